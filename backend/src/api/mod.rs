@@ -1,6 +1,7 @@
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
+    body::Bytes,
+    extract::{FromRef, Path, Query, State},
+    http::{HeaderMap, StatusCode},
     response::IntoResponse,
     Json,
 };
@@ -9,31 +10,156 @@ use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
-use crate::git::{analyze_directory, analyze_repository, find_repositories};
-use crate::github::analyze_github_repo;
+use crate::cache::CloneCache;
+use crate::compare::compare;
+use crate::config::Config;
+use crate::executor::{AnalysisExecutor, ExecutorError};
+use crate::git::{analyze_directory, analyze_repository, DirectoryAnalysis, GitError, ScanOptions};
+use crate::impact::{branch_impact, ImpactError};
+use crate::jobs::{JobGuard, JobRegistry};
 use crate::models::{
-    AnalyzeGithubRequest, AnalyzeLocalRequest, ErrorResponse, RepoAnalysis, ScanDirectoryRequest,
-    ScanDirectoryResponse,
+    AnalyzeLocalRequest, AnalyzeRemoteOwnerRequest, AnalyzeRemoteRequest, AnalyzeUrlRequest,
+    CompareQuery, ErrorResponse, ImpactQuery, ListReposQuery, PurgeCacheQuery, RepoAnalysis,
+    RepoListResponse, RepoSort, RepoSummary, ScanDirectoryRequest, ScanDirectoryResponse,
+    ScanFailure, SortOrder, TreeQuery, UpdateRepoRequest, WebhookResponse,
+};
+use crate::provider::{analyze_provider_owner, analyze_remote_request, ProviderError, Providers};
+use crate::refresh::reanalyze;
+use crate::remote::{
+    analyze_remote, redact_url, repo_name_from_url, validate_url, RemoteCredentials, RemoteError,
+};
+use crate::schedule::Schedule;
+use crate::sources::{AddSourceRequest, SourceKind, SourceRegistry};
+use crate::summary::summarize;
+use crate::tree::{query_tree, TreeError};
+use crate::webhooks::{
+    generic_push_urls, github_push_urls, matches_push, WebhookConfig, WebhookError,
 };
 
 pub type RepoStore = Arc<RwLock<HashMap<String, RepoAnalysis>>>;
 
+/// Analyses replaced by a re-analysis, keyed by id, kept for comparison
+pub type PreviousStore = Arc<RwLock<HashMap<String, RepoAnalysis>>>;
+
+/// Most recent scan failures, keyed by repository path
+pub type FailureStore = Arc<RwLock<HashMap<String, ScanFailure>>>;
+
+#[derive(Clone, FromRef)]
+pub struct AppState {
+    pub repos: RepoStore,
+    pub failures: FailureStore,
+    pub executor: AnalysisExecutor,
+    pub jobs: JobRegistry,
+    pub providers: Providers,
+    pub cache: CloneCache,
+    pub webhooks: WebhookConfig,
+    pub sources: SourceRegistry,
+    #[from_ref(skip)]
+    pub previous: PreviousStore,
+}
+
+impl AppState {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            repos: RepoStore::default(),
+            failures: FailureStore::default(),
+            executor: AnalysisExecutor::new(config),
+            jobs: JobRegistry::default(),
+            providers: Providers::from_config(config),
+            cache: CloneCache::new(config),
+            webhooks: WebhookConfig::from_config(config),
+            sources: SourceRegistry::default(),
+            previous: PreviousStore::default(),
+        }
+    }
+}
+
+fn git_error_status(e: &GitError) -> StatusCode {
+    match e {
+        GitError::Executor(ExecutorError::QueueFull { .. }) => StatusCode::SERVICE_UNAVAILABLE,
+        GitError::Executor(ExecutorError::Task(_)) => StatusCode::INTERNAL_SERVER_ERROR,
+        GitError::Cancelled => StatusCode::CONFLICT,
+        _ => StatusCode::BAD_REQUEST,
+    }
+}
+
+fn remote_error_status(e: &RemoteError) -> StatusCode {
+    match e {
+        RemoteError::Analysis(e) => git_error_status(e),
+        RemoteError::Auth(_) => StatusCode::FORBIDDEN,
+        RemoteError::NotFound(_) => StatusCode::NOT_FOUND,
+        RemoteError::Network(_) => StatusCode::BAD_GATEWAY,
+        RemoteError::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        RemoteError::Cancelled => StatusCode::CONFLICT,
+        _ => StatusCode::BAD_REQUEST,
+    }
+}
+
+fn provider_error_status(e: &ProviderError) -> StatusCode {
+    match e {
+        ProviderError::Api {
+            status: Some(404), ..
+        } => StatusCode::NOT_FOUND,
+        ProviderError::Analysis(e) => git_error_status(e),
+        ProviderError::Remote(e) => remote_error_status(e),
+        ProviderError::Cancelled => StatusCode::CONFLICT,
+        _ => StatusCode::BAD_REQUEST,
+    }
+}
+
+fn webhook_error_status(e: &WebhookError) -> StatusCode {
+    match e {
+        WebhookError::Disabled => StatusCode::FORBIDDEN,
+        WebhookError::MissingSignature | WebhookError::InvalidSignature => StatusCode::UNAUTHORIZED,
+        WebhookError::Payload(_) => StatusCode::BAD_REQUEST,
+    }
+}
+
+/// Register a cancellable job for the duration of a request
+fn start_job(
+    jobs: &JobRegistry,
+    id: Option<String>,
+    kind: &str,
+    target: &str,
+) -> Result<JobGuard, (StatusCode, Json<ErrorResponse>)> {
+    let requested = id.clone();
+    jobs.start(id, kind, target).ok_or_else(|| {
+        (
+            StatusCode::CONFLICT,
+            Json(ErrorResponse {
+                error: format!("Job {} is already running", requested.unwrap_or_default()),
+            }),
+        )
+    })
+}
+
 pub async fn health() -> impl IntoResponse {
     Json(json!({ "status": "ok" }))
 }
 
 pub async fn analyze_local(
     State(store): State<RepoStore>,
+    State(executor): State<AnalysisExecutor>,
+    State(jobs): State<JobRegistry>,
     Json(request): Json<AnalyzeLocalRequest>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
-    match analyze_repository(&request.path) {
+    let job = start_job(&jobs, request.job_id, "analyze_local", &request.path)?;
+    let path = request.path;
+    let cancel = job.token().clone();
+    let result = executor
+        .run(move || analyze_repository(std::path::Path::new(&path), false, &cancel))
+        .await
+        .map_err(GitError::from)
+        .and_then(|result| result);
+
+    match result {
         Ok(analysis) => {
             let id = analysis.id.clone();
             store.write().await.insert(id.clone(), analysis.clone());
             Ok((StatusCode::OK, Json(analysis)))
         }
         Err(e) => Err((
-            StatusCode::BAD_REQUEST,
+            git_error_status(&e),
             Json(ErrorResponse {
                 error: e.to_string(),
             }),
@@ -41,18 +167,25 @@ pub async fn analyze_local(
     }
 }
 
-pub async fn analyze_github(
+pub async fn analyze_remote_repo(
     State(store): State<RepoStore>,
-    Json(request): Json<AnalyzeGithubRequest>,
+    State(executor): State<AnalysisExecutor>,
+    State(jobs): State<JobRegistry>,
+    State(providers): State<Providers>,
+    State(cache): State<CloneCache>,
+    Json(request): Json<AnalyzeRemoteRequest>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
-    match analyze_github_repo(&request.owner, &request.repo).await {
+    let target = format!("{}/{}", request.owner, request.repo);
+    let job = start_job(&jobs, request.job_id.clone(), "analyze_remote", &target)?;
+    let result = analyze_remote_request(&providers, &request, &cache, &executor, job.token()).await;
+    match result {
         Ok(analysis) => {
             let id = analysis.id.clone();
             store.write().await.insert(id.clone(), analysis.clone());
             Ok((StatusCode::OK, Json(analysis)))
         }
         Err(e) => Err((
-            StatusCode::BAD_REQUEST,
+            provider_error_status(&e),
             Json(ErrorResponse {
                 error: e.to_string(),
             }),
@@ -60,6 +193,55 @@ pub async fn analyze_github(
     }
 }
 
+pub async fn analyze_url(
+    State(store): State<RepoStore>,
+    State(executor): State<AnalysisExecutor>,
+    State(jobs): State<JobRegistry>,
+    State(cache): State<CloneCache>,
+    Json(request): Json<AnalyzeUrlRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let to_response = |e: RemoteError| {
+        (
+            remote_error_status(&e),
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )
+    };
+    let url = request.url.trim();
+    validate_url(url).map_err(to_response)?;
+    let job = start_job(
+        &jobs,
+        request.job_id.clone(),
+        "analyze_url",
+        &redact_url(url),
+    )?;
+    let name = request.name.unwrap_or_else(|| repo_name_from_url(url));
+    // Only a token from the request is used: the configured GitHub token must
+    // not be sent to arbitrary hosts
+    let credentials = RemoteCredentials {
+        username: request.username,
+        token: request.token,
+    };
+
+    let analysis = analyze_remote(
+        url,
+        &name,
+        &credentials,
+        request.clone_strategy,
+        &cache,
+        &executor,
+        job.token(),
+    )
+    .await
+    .map_err(to_response)?;
+    store
+        .write()
+        .await
+        .insert(analysis.id.clone(), analysis.clone());
+    Ok((StatusCode::OK, Json(analysis)))
+}
+
 pub async fn get_repo(
     State(store): State<RepoStore>,
     Path(id): Path<String>,
@@ -76,13 +258,53 @@ pub async fn get_repo(
     }
 }
 
+/// A repository's directory tree, optionally narrowed to a subtree and trimmed
 pub async fn get_repo_tree(
     State(store): State<RepoStore>,
     Path(id): Path<String>,
+    Query(query): Query<TreeQuery>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
     let repos = store.read().await;
-    match repos.get(&id) {
-        Some(repo) => Ok(Json(repo.directories.clone())),
+    let repo = repos.get(&id).ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "Repository not found".to_string(),
+            }),
+        )
+    })?;
+
+    query_tree(&repo.directories, &query)
+        .map(Json)
+        .map_err(|e| {
+            let status = match e {
+                TreeError::PathNotFound(_) => StatusCode::NOT_FOUND,
+                TreeError::InvalidDepth => StatusCode::BAD_REQUEST,
+            };
+            (
+                status,
+                Json(ErrorResponse {
+                    error: e.to_string(),
+                }),
+            )
+        })
+}
+
+/// Set or clear the name a repository is shown with
+pub async fn update_repo(
+    State(store): State<RepoStore>,
+    Path(id): Path<String>,
+    Json(request): Json<UpdateRepoRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let mut repos = store.write().await;
+    match repos.get_mut(&id) {
+        Some(repo) => {
+            repo.display_name = request
+                .display_name
+                .map(|name| name.trim().to_string())
+                .filter(|name| !name.is_empty());
+            Ok(Json(repo.clone()))
+        }
         None => Err((
             StatusCode::NOT_FOUND,
             Json(ErrorResponse {
@@ -92,52 +314,261 @@ pub async fn get_repo_tree(
     }
 }
 
-pub async fn list_repos(State(store): State<RepoStore>) -> impl IntoResponse {
+/// Remove a repository along with its submodules
+pub async fn delete_repo(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let mut repos = state.repos.write().await;
+    if !repos.contains_key(&id) {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "Repository not found".to_string(),
+            }),
+        ));
+    }
+
+    let mut removed = vec![id];
+    let mut next = 0;
+    while next < removed.len() {
+        let parent_id = removed[next].clone();
+        removed.extend(
+            repos
+                .values()
+                .filter(|repo| repo.parent_id.as_ref() == Some(&parent_id))
+                .map(|repo| repo.id.clone()),
+        );
+        next += 1;
+    }
+
+    let mut failures = state.failures.write().await;
+    let mut previous = state.previous.write().await;
+    for id in &removed {
+        if let Some(repo) = repos.remove(id) {
+            failures.remove(&repo.path);
+        }
+        previous.remove(id);
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Remove every stored repository and recorded failure
+pub async fn clear_repos(State(state): State<AppState>) -> impl IntoResponse {
+    let mut repos = state.repos.write().await;
+    let removed = repos.len();
+    repos.clear();
+    state.previous.write().await.clear();
+    state.failures.write().await.clear();
+    Json(json!({ "removed": removed }))
+}
+
+/// Re-analyze a stored repository from its recorded path or remote, keeping its id
+pub async fn refresh_repo(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let previous = state.repos.read().await.get(&id).cloned().ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "Repository not found".to_string(),
+            }),
+        )
+    })?;
+    let job = start_job(
+        &state.jobs,
+        Some(format!("refresh-{}", id)),
+        "refresh",
+        &previous.name,
+    )?;
+
+    let result = reanalyze(
+        &previous,
+        &state.providers,
+        &state.cache,
+        &state.executor,
+        job.token(),
+    )
+    .await;
+    match store_reanalysis(&state, &previous, result).await {
+        Ok(analysis) => Ok(Json(analysis)),
+        Err(e) => Err((
+            provider_error_status(&e),
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+/// List repositories as summaries, filtered, sorted and paginated
+pub async fn list_repos(
+    State(store): State<RepoStore>,
+    Query(query): Query<ListReposQuery>,
+) -> impl IntoResponse {
     let repos = store.read().await;
-    let list: Vec<RepoAnalysis> = repos.values().cloned().collect();
-    Json(list)
+    let shown_name = |repo: &RepoAnalysis| {
+        repo.display_name
+            .as_deref()
+            .unwrap_or(&repo.name)
+            .to_lowercase()
+    };
+    let name_filter = query.name.as_ref().map(|name| name.to_lowercase());
+
+    let mut matching: Vec<&RepoAnalysis> = repos
+        .values()
+        .filter(|repo| {
+            query.language.as_ref().is_none_or(|language| {
+                repo.primary_language()
+                    .is_some_and(|primary| primary.eq_ignore_ascii_case(language))
+            })
+        })
+        .filter(|repo| {
+            name_filter.as_ref().is_none_or(|filter| {
+                repo.name.to_lowercase().contains(filter) || shown_name(repo).contains(filter)
+            })
+        })
+        .filter(|repo| query.min_lines.is_none_or(|min| repo.total_lines >= min))
+        .filter(|repo| query.max_lines.is_none_or(|max| repo.total_lines <= max))
+        .collect();
+
+    let order = query.order.unwrap_or(match query.sort {
+        RepoSort::Name | RepoSort::Language => SortOrder::Asc,
+        RepoSort::Lines | RepoSort::Age => SortOrder::Desc,
+    });
+    matching.sort_by(|a, b| {
+        let ordering = match query.sort {
+            RepoSort::Name => shown_name(a).cmp(&shown_name(b)),
+            RepoSort::Lines => a.total_lines.cmp(&b.total_lines),
+            RepoSort::Age => a.age_days.cmp(&b.age_days),
+            RepoSort::Language => match (a.primary_language(), b.primary_language()) {
+                (Some(a_language), Some(b_language)) => a_language.cmp(b_language),
+                // Repositories without code sort last in either order
+                (a_language, b_language) => {
+                    return a_language
+                        .is_none()
+                        .cmp(&b_language.is_none())
+                        .then_with(|| a.id.cmp(&b.id))
+                }
+            },
+        };
+        let ordering = match order {
+            SortOrder::Asc => ordering,
+            SortOrder::Desc => ordering.reverse(),
+        };
+        // Ties keep a stable order across pages
+        ordering.then_with(|| a.id.cmp(&b.id))
+    });
+
+    let include_tree = query
+        .include
+        .as_deref()
+        .is_some_and(|include| include.split(',').any(|extra| extra.trim() == "tree"));
+    let total = matching.len();
+    let page = matching
+        .into_iter()
+        .skip(query.offset)
+        .take(query.limit.unwrap_or(usize::MAX))
+        .map(|repo| RepoSummary::new(repo, include_tree))
+        .collect();
+
+    Json(RepoListResponse {
+        repos: page,
+        total,
+        offset: query.offset,
+        limit: query.limit,
+    })
+}
+
+pub async fn get_summary(
+    State(store): State<RepoStore>,
+    State(failures): State<FailureStore>,
+) -> impl IntoResponse {
+    let repos: Vec<RepoAnalysis> = store.read().await.values().cloned().collect();
+    let failures: Vec<ScanFailure> = failures.read().await.values().cloned().collect();
+    Json(summarize(&repos, failures))
 }
 
 pub async fn scan_directory(
     State(store): State<RepoStore>,
+    State(failure_store): State<FailureStore>,
+    State(executor): State<AnalysisExecutor>,
+    State(jobs): State<JobRegistry>,
     Json(request): Json<ScanDirectoryRequest>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let job = start_job(&jobs, request.job_id.clone(), "scan", &request.path)?;
     let path = request.path.clone();
+    let options = ScanOptions::from_request(&request).map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )
+    })?;
 
-    // Run CPU-bound work in a blocking task to not block the async runtime
-    let result = tokio::task::spawn_blocking(move || {
-        // First find all repositories
-        let repo_paths = find_repositories(&path)?;
-        let total_found = repo_paths.len();
+    // CPU-bound analysis runs on the bounded executor, not the async runtime
+    let result = analyze_directory(&path, options, &executor, job.token())
+        .await
+        .map_err(|e| {
+            (
+                git_error_status(&e),
+                Json(ErrorResponse {
+                    error: e.to_string(),
+                }),
+            )
+        })?;
 
-        // Analyze all found repositories in parallel using rayon
-        let analyses = analyze_directory(&path)?;
-        let total_analyzed = analyses.len();
+    let response = store_directory_analysis(&store, &failure_store, result).await;
+    Ok((StatusCode::OK, Json(response)))
+}
 
-        Ok::<_, crate::git::GitError>((analyses, total_found, total_analyzed))
-    })
-    .await;
+pub async fn analyze_remote_owner(
+    State(store): State<RepoStore>,
+    State(failure_store): State<FailureStore>,
+    State(executor): State<AnalysisExecutor>,
+    State(jobs): State<JobRegistry>,
+    State(providers): State<Providers>,
+    State(cache): State<CloneCache>,
+    Json(request): Json<AnalyzeRemoteOwnerRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let job = start_job(
+        &jobs,
+        request.job_id.clone(),
+        "analyze_remote_owner",
+        &request.owner,
+    )?;
+    let provider = providers.get(request.provider, request.token.clone());
 
-    // Handle spawn_blocking join error
-    let (analyses, total_found, total_analyzed) = match result {
-        Ok(Ok(data)) => data,
-        Ok(Err(e)) => {
-            return Err((
-                StatusCode::BAD_REQUEST,
+    let result = analyze_provider_owner(provider, &request, &cache, &executor, job.token())
+        .await
+        .map_err(|e| {
+            (
+                provider_error_status(&e),
                 Json(ErrorResponse {
                     error: e.to_string(),
                 }),
-            ))
-        }
-        Err(e) => {
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    error: format!("Task failed: {}", e),
-                }),
-            ))
-        }
-    };
+            )
+        })?;
+
+    let response = store_directory_analysis(&store, &failure_store, result).await;
+    Ok((StatusCode::OK, Json(response)))
+}
+
+/// Store the results of a multi-repository analysis and build the response
+pub async fn store_directory_analysis(
+    store: &RepoStore,
+    failure_store: &FailureStore,
+    result: DirectoryAnalysis,
+) -> ScanDirectoryResponse {
+    let DirectoryAnalysis {
+        repos: analyses,
+        failures,
+        total_found,
+        truncated,
+    } = result;
+    let total_analyzed = analyses.len();
 
     // Store all analyzed repos
     {
@@ -147,12 +578,372 @@ pub async fn scan_directory(
         }
     }
 
-    Ok((
-        StatusCode::OK,
-        Json(ScanDirectoryResponse {
-            repos: analyses,
-            total_found,
-            total_analyzed,
-        }),
-    ))
+    // Remember failures so the portfolio summary can report them
+    {
+        let mut failure_store = failure_store.write().await;
+        for analysis in &analyses {
+            failure_store.remove(&analysis.path);
+        }
+        for failure in &failures {
+            failure_store.insert(failure.path.clone(), failure.clone());
+        }
+    }
+
+    let summary = summarize(&analyses, failures.clone());
+
+    ScanDirectoryResponse {
+        repos: analyses,
+        total_found,
+        total_analyzed,
+        truncated,
+        failures,
+        summary,
+    }
+}
+
+pub async fn list_jobs(State(jobs): State<JobRegistry>) -> impl IntoResponse {
+    Json(jobs.list())
+}
+
+pub async fn cancel_job(
+    State(jobs): State<JobRegistry>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    if jobs.cancel(&id) {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "Job not found".to_string(),
+            }),
+        ))
+    }
+}
+
+pub async fn get_cache(State(cache): State<CloneCache>) -> impl IntoResponse {
+    Json(cache.status())
+}
+
+/// Remove cached clones that are not in use, or only the one for `url`
+pub async fn purge_cache(
+    State(cache): State<CloneCache>,
+    Query(query): Query<PurgeCacheQuery>,
+) -> impl IntoResponse {
+    let removed = cache.purge(query.url).await;
+    Json(json!({ "removed": removed }))
+}
+
+/// Re-analyze stored repositories after a GitHub push to their default branch
+pub async fn github_webhook(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+    let urls = state
+        .webhooks
+        .verify(header("x-hub-signature-256"), &body)
+        .and_then(|_| github_push_urls(header("x-github-event"), &body))
+        .map_err(|e| {
+            (
+                webhook_error_status(&e),
+                Json(ErrorResponse {
+                    error: e.to_string(),
+                }),
+            )
+        })?;
+
+    let queued = match urls {
+        Some(urls) => queue_push_refreshes(&state, &urls).await,
+        None => Vec::new(),
+    };
+    Ok((StatusCode::ACCEPTED, Json(WebhookResponse { queued })))
+}
+
+/// Re-analyze stored repositories after a push reported by any other service.
+///
+/// The body is `{"url": ...}` signed like GitHub's, in `X-CodeCity-Signature-256`.
+pub async fn generic_webhook(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let signature = headers
+        .get("x-codecity-signature-256")
+        .and_then(|value| value.to_str().ok());
+    let urls = state
+        .webhooks
+        .verify(signature, &body)
+        .and_then(|_| generic_push_urls(&body))
+        .map_err(|e| {
+            (
+                webhook_error_status(&e),
+                Json(ErrorResponse {
+                    error: e.to_string(),
+                }),
+            )
+        })?;
+
+    let queued = queue_push_refreshes(&state, &urls).await;
+    Ok((StatusCode::ACCEPTED, Json(WebhookResponse { queued })))
+}
+
+/// Queue re-analysis of every stored repository matching a push
+async fn queue_push_refreshes(state: &AppState, urls: &[String]) -> Vec<String> {
+    let matched: Vec<RepoAnalysis> = state
+        .repos
+        .read()
+        .await
+        .values()
+        .filter(|analysis| matches_push(analysis, urls))
+        .cloned()
+        .collect();
+    tracing::info!(
+        "Push to {} matches {} stored repositories",
+        urls.join(", "),
+        matched.len()
+    );
+
+    matched
+        .into_iter()
+        .filter_map(|analysis| {
+            let id = analysis.id.clone();
+            queue_refresh(state, analysis).then_some(id)
+        })
+        .collect()
+}
+
+/// Re-analyze a stored repository in the background.
+///
+/// Returns `false` if a refresh of the repository is already running.
+fn queue_refresh(state: &AppState, previous: RepoAnalysis) -> bool {
+    let Some(job) = state.jobs.start(
+        Some(format!("refresh-{}", previous.id)),
+        "refresh",
+        &previous.name,
+    ) else {
+        return false;
+    };
+
+    let state = state.clone();
+    tokio::spawn(async move {
+        let result = reanalyze(
+            &previous,
+            &state.providers,
+            &state.cache,
+            &state.executor,
+            job.token(),
+        )
+        .await;
+        if let Err(e) = store_reanalysis(&state, &previous, result).await {
+            tracing::warn!("Failed to refresh {}: {}", previous.name, e);
+        }
+    });
+    true
+}
+
+/// Replace a stored analysis with its re-analysis, or record why it failed.
+///
+/// The replaced analysis is kept for comparison. On failure the previous
+/// analysis stays in place, and a repository deleted while it was being
+/// re-analyzed is not brought back.
+async fn store_reanalysis(
+    state: &AppState,
+    previous: &RepoAnalysis,
+    result: Result<RepoAnalysis, ProviderError>,
+) -> Result<RepoAnalysis, ProviderError> {
+    match &result {
+        Ok(analysis) => {
+            if let Some(stored) = state.repos.write().await.get_mut(&analysis.id) {
+                let replaced = std::mem::replace(stored, analysis.clone());
+                state
+                    .previous
+                    .write()
+                    .await
+                    .insert(replaced.id.clone(), replaced);
+            }
+            state.failures.write().await.remove(&previous.path);
+        }
+        Err(ProviderError::Cancelled) => {}
+        Err(e) => {
+            state.failures.write().await.insert(
+                previous.path.clone(),
+                ScanFailure {
+                    path: previous.path.clone(),
+                    error_kind: e.kind(),
+                    message: e.to_string(),
+                },
+            );
+        }
+    }
+    result
+}
+
+/// Give new analyses the ids and display names of earlier analyses of the
+/// same paths, keeping the analyses they replace for comparison
+pub async fn keep_previous_analyses(
+    state: &AppState,
+    repos: &mut [RepoAnalysis],
+    previous_ids: &[String],
+) {
+    let stored = state.repos.read().await;
+    let by_path: HashMap<&str, &RepoAnalysis> = previous_ids
+        .iter()
+        .filter_map(|id| stored.get(id))
+        .map(|analysis| (analysis.path.as_str(), analysis))
+        .collect();
+
+    let mut previous = state.previous.write().await;
+    let mut renamed = HashMap::new();
+    for repo in repos.iter_mut() {
+        if let Some(old) = by_path.get(repo.path.as_str()) {
+            renamed.insert(
+                std::mem::replace(&mut repo.id, old.id.clone()),
+                old.id.clone(),
+            );
+            repo.display_name = old.display_name.clone();
+            previous.insert(old.id.clone(), (*old).clone());
+        }
+    }
+    // Submodules refer to their parent by id
+    for repo in repos.iter_mut() {
+        if let Some(parent_id) = repo.parent_id.as_ref().and_then(|id| renamed.get(id)) {
+            repo.parent_id = Some(parent_id.clone());
+        }
+    }
+}
+
+/// The analysis a repository had before its latest re-analysis
+pub async fn get_previous_repo(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let previous = state.previous.read().await;
+    match previous.get(&id) {
+        Some(repo) => Ok(Json(repo.clone())),
+        None => Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "No previous analysis of this repository".to_string(),
+            }),
+        )),
+    }
+}
+
+/// Files and lines a branch changes in each directory of a local repository
+pub async fn get_repo_impact(
+    State(store): State<RepoStore>,
+    State(executor): State<AnalysisExecutor>,
+    Path(id): Path<String>,
+    Query(query): Query<ImpactQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let repo = store.read().await.get(&id).cloned().ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "Repository not found".to_string(),
+            }),
+        )
+    })?;
+
+    let result = if repo.remote.is_some() || repo.clone_strategy.is_some() {
+        Err(ImpactError::NotLocal)
+    } else {
+        executor
+            .run(move || branch_impact(&repo.path, &query.base, &query.head))
+            .await
+            .map_err(ImpactError::from)
+            .and_then(|result| result)
+    };
+
+    result.map(Json).map_err(|e| {
+        let status = match e {
+            ImpactError::Executor(ExecutorError::QueueFull { .. }) => {
+                StatusCode::SERVICE_UNAVAILABLE
+            }
+            ImpactError::Git(_) | ImpactError::Executor(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::BAD_REQUEST,
+        };
+        (
+            status,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )
+    })
+}
+
+/// Differences between two analyses, or between a repository and its previous analysis
+pub async fn compare_repos(
+    State(state): State<AppState>,
+    Query(query): Query<CompareQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let not_found = |error: &str| {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: error.to_string(),
+            }),
+        )
+    };
+
+    let repos = state.repos.read().await;
+    let head = repos
+        .get(&query.head)
+        .ok_or_else(|| not_found("Repository not found"))?;
+    let comparison = match &query.base {
+        Some(base) => {
+            let base = repos
+                .get(base)
+                .ok_or_else(|| not_found("Base repository not found"))?;
+            compare(base, head)
+        }
+        None => {
+            let previous = state.previous.read().await;
+            let base = previous
+                .get(&query.head)
+                .ok_or_else(|| not_found("No previous analysis of this repository"))?;
+            compare(base, head)
+        }
+    };
+    Ok(Json(comparison))
+}
+
+pub async fn list_sources(State(sources): State<SourceRegistry>) -> impl IntoResponse {
+    Json(sources.list())
+}
+
+/// Track a source for scheduled re-analysis
+pub async fn add_source(
+    State(sources): State<SourceRegistry>,
+    Json(request): Json<AddSourceRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let bad_request = |error: String| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error }));
+    let schedule: Schedule = request
+        .schedule
+        .parse()
+        .map_err(|e: crate::schedule::ScheduleError| bad_request(e.to_string()))?;
+    if let SourceKind::Scan(scan) = &request.source {
+        ScanOptions::from_request(scan).map_err(|e| bad_request(e.to_string()))?;
+    }
+
+    let source = sources.add(request.source, schedule, request.run_now);
+    Ok((StatusCode::CREATED, Json(source)))
+}
+
+pub async fn delete_source(
+    State(sources): State<SourceRegistry>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    if sources.remove(&id) {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: "Source not found".to_string(),
+            }),
+        ))
+    }
 }
@@ -0,0 +1,409 @@
+use crate::config::Config;
+use crate::models::CloneStrategy;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::sync::OwnedMutexGuard;
+use walkdir::WalkDir;
+
+/// A cached clone as reported by `GET /api/cache`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheEntryInfo {
+    pub url: String,
+    #[serde(default)]
+    pub strategy: CloneStrategy,
+    pub size_bytes: u64,
+    pub created_at: DateTime<Utc>,
+    pub last_used: DateTime<Utc>,
+    /// Whether an analysis is currently using the clone
+    #[serde(default, skip_deserializing)]
+    pub in_use: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CacheStatus {
+    pub dir: String,
+    pub max_bytes: u64,
+    pub total_bytes: u64,
+    pub entries: Vec<CacheEntryInfo>,
+}
+
+/// On-disk cache of cloned remote repositories, keyed by URL.
+///
+/// Each entry is a directory the caller clones into or refreshes, plus a JSON
+/// file recording its URL, size and last use. An entry is locked while a
+/// checkout holds it, so a clone is never refreshed or evicted in the middle
+/// of an analysis. When the total size exceeds the budget, the least recently
+/// used entries that are not in use are removed.
+#[derive(Clone)]
+pub struct CloneCache {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    dir: PathBuf,
+    max_bytes: u64,
+    entries: Mutex<HashMap<String, Arc<Slot>>>,
+}
+
+struct Slot {
+    key: String,
+    lock: Arc<tokio::sync::Mutex<()>>,
+    info: Mutex<Option<CacheEntryInfo>>,
+}
+
+/// Exclusive use of a cache entry.
+///
+/// Dropping a checkout that never finished removes a partial clone, and
+/// gives the cache a chance to evict entries over the size budget.
+pub struct CacheCheckout {
+    inner: Arc<Inner>,
+    slot: Arc<Slot>,
+    path: PathBuf,
+    populated: bool,
+    lock: Option<OwnedMutexGuard<()>>,
+}
+
+impl CacheCheckout {
+    /// Directory holding the clone
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether the directory holds a complete clone from an earlier request
+    pub fn is_populated(&self) -> bool {
+        self.populated
+    }
+
+    /// Record a successful clone or refresh
+    pub async fn finish(&mut self) -> std::io::Result<()> {
+        let path = self.path.clone();
+        let size_bytes = tokio::task::spawn_blocking(move || directory_size(&path))
+            .await
+            .map_err(std::io::Error::other)?;
+
+        let now = Utc::now();
+        let info = {
+            let mut info = self.slot.info.lock().unwrap();
+            let entry = info
+                .as_mut()
+                .expect("checked out entries always have metadata");
+            entry.size_bytes = size_bytes;
+            entry.last_used = now;
+            entry.clone()
+        };
+
+        let json = serde_json::to_vec_pretty(&info).map_err(std::io::Error::other)?;
+        tokio::fs::write(self.inner.meta_path(&self.slot.key), json).await?;
+        self.populated = true;
+        Ok(())
+    }
+}
+
+impl Drop for CacheCheckout {
+    fn drop(&mut self) {
+        let lock = self.lock.take();
+        let inner = self.inner.clone();
+        let slot = self.slot.clone();
+        let populated = self.populated;
+        tokio::task::spawn_blocking(move || {
+            if !populated {
+                inner.remove(&slot);
+            }
+            // Release the entry before evicting so it can be evicted too
+            drop(lock);
+            inner.evict();
+        });
+    }
+}
+
+impl CloneCache {
+    pub fn new(config: &Config) -> Self {
+        let inner = Inner {
+            dir: config.cache_dir.clone(),
+            max_bytes: config.cache_max_bytes,
+            entries: Mutex::new(HashMap::new()),
+        };
+        if let Err(e) = inner.load() {
+            tracing::warn!(
+                "Failed to load clone cache from {}: {}",
+                inner.dir.display(),
+                e
+            );
+        }
+        tracing::info!(
+            "Clone cache: {} ({} of {} bytes used)",
+            inner.dir.display(),
+            inner.total_bytes(),
+            inner.max_bytes
+        );
+
+        Self {
+            inner: Arc::new(inner),
+        }
+    }
+
+    /// Lock the cache entry for a URL and clone strategy, waiting for any
+    /// other user to finish
+    pub async fn checkout(
+        &self,
+        url: &str,
+        strategy: CloneStrategy,
+    ) -> std::io::Result<CacheCheckout> {
+        let key = cache_key(url, strategy);
+        let (slot, lock) = loop {
+            let slot = {
+                let mut entries = self.inner.entries.lock().unwrap();
+                entries
+                    .entry(key.clone())
+                    .or_insert_with(|| {
+                        Arc::new(Slot {
+                            key: key.clone(),
+                            lock: Arc::default(),
+                            info: Mutex::new(None),
+                        })
+                    })
+                    .clone()
+            };
+            let lock = slot.lock.clone().lock_owned().await;
+            // The entry may have been evicted while we waited for it
+            if self.inner.is_current(&slot) {
+                break (slot, lock);
+            }
+        };
+
+        let now = Utc::now();
+        let populated = {
+            let mut info = slot.info.lock().unwrap();
+            match info.as_mut() {
+                Some(entry) => {
+                    entry.last_used = now;
+                    true
+                }
+                None => {
+                    *info = Some(CacheEntryInfo {
+                        url: url.to_string(),
+                        strategy,
+                        size_bytes: 0,
+                        created_at: now,
+                        last_used: now,
+                        in_use: false,
+                    });
+                    false
+                }
+            }
+        };
+
+        let path = self.inner.dir.join(&key);
+        if !populated {
+            // Start from an empty directory in case an earlier clone was interrupted
+            let stale = path.clone();
+            tokio::task::spawn_blocking(move || {
+                let _ = std::fs::remove_dir_all(&stale);
+            })
+            .await
+            .map_err(std::io::Error::other)?;
+            tokio::fs::create_dir_all(&path).await?;
+        }
+
+        Ok(CacheCheckout {
+            inner: self.inner.clone(),
+            slot,
+            path,
+            populated,
+            lock: Some(lock),
+        })
+    }
+
+    pub fn status(&self) -> CacheStatus {
+        let mut entries: Vec<CacheEntryInfo> = self
+            .inner
+            .entries
+            .lock()
+            .unwrap()
+            .values()
+            .filter_map(|slot| {
+                let mut info = slot.info.lock().unwrap().clone()?;
+                info.in_use = slot.lock.try_lock().is_err();
+                Some(info)
+            })
+            .collect();
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.last_used));
+
+        CacheStatus {
+            dir: self.inner.dir.display().to_string(),
+            max_bytes: self.inner.max_bytes,
+            total_bytes: entries.iter().map(|entry| entry.size_bytes).sum(),
+            entries,
+        }
+    }
+
+    /// Remove cached clones, or only those of `url`, skipping entries in use.
+    ///
+    /// Returns the number of entries removed.
+    pub async fn purge(&self, url: Option<String>) -> usize {
+        let inner = self.inner.clone();
+        tokio::task::spawn_blocking(move || {
+            let slots: Vec<Arc<Slot>> = inner
+                .entries
+                .lock()
+                .unwrap()
+                .values()
+                .filter(|slot| match &url {
+                    Some(url) => slot
+                        .info
+                        .lock()
+                        .unwrap()
+                        .as_ref()
+                        .is_some_and(|info| &info.url == url),
+                    None => true,
+                })
+                .cloned()
+                .collect();
+            slots
+                .iter()
+                .filter(|slot| inner.remove_if_unused(slot))
+                .count()
+        })
+        .await
+        .unwrap_or(0)
+    }
+}
+
+impl Inner {
+    fn meta_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+
+    /// Index entries left by a previous run and remove incomplete clones
+    fn load(&self) -> std::io::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let mut entries = self.entries.lock().unwrap();
+        for dir_entry in std::fs::read_dir(&self.dir)? {
+            let path = dir_entry?.path();
+            if path.is_dir() {
+                let key = path.file_name().unwrap().to_string_lossy().to_string();
+                if !self.meta_path(&key).exists() {
+                    tracing::debug!("Removing incomplete clone {}", path.display());
+                    let _ = std::fs::remove_dir_all(&path);
+                }
+                continue;
+            }
+            if path.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+            let key = path.file_stem().unwrap().to_string_lossy().to_string();
+            let info = std::fs::read(&path)
+                .ok()
+                .and_then(|json| serde_json::from_slice::<CacheEntryInfo>(&json).ok());
+            match info {
+                Some(info) if self.dir.join(&key).is_dir() => {
+                    entries.insert(
+                        key.clone(),
+                        Arc::new(Slot {
+                            key,
+                            lock: Arc::default(),
+                            info: Mutex::new(Some(info)),
+                        }),
+                    );
+                }
+                _ => {
+                    let _ = std::fs::remove_file(&path);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn total_bytes(&self) -> u64 {
+        self.entries
+            .lock()
+            .unwrap()
+            .values()
+            .filter_map(|slot| slot.info.lock().unwrap().as_ref().map(|i| i.size_bytes))
+            .sum()
+    }
+
+    /// Remove least recently used entries until the cache fits its budget
+    fn evict(&self) {
+        let mut total = self.total_bytes();
+        if total <= self.max_bytes {
+            return;
+        }
+
+        let mut candidates: Vec<(DateTime<Utc>, u64, Arc<Slot>)> = self
+            .entries
+            .lock()
+            .unwrap()
+            .values()
+            .filter_map(|slot| {
+                let info = slot.info.lock().unwrap();
+                info.as_ref()
+                    .map(|info| (info.last_used, info.size_bytes, slot.clone()))
+            })
+            .collect();
+        candidates.sort_by_key(|(last_used, _, _)| *last_used);
+
+        for (_, size_bytes, slot) in candidates {
+            if total <= self.max_bytes {
+                break;
+            }
+            if self.remove_if_unused(&slot) {
+                tracing::info!("Evicted {} bytes from clone cache", size_bytes);
+                total = total.saturating_sub(size_bytes);
+            }
+        }
+    }
+
+    fn is_current(&self, slot: &Arc<Slot>) -> bool {
+        self.entries
+            .lock()
+            .unwrap()
+            .get(&slot.key)
+            .is_some_and(|current| Arc::ptr_eq(current, slot))
+    }
+
+    fn remove_if_unused(&self, slot: &Arc<Slot>) -> bool {
+        match slot.lock.try_lock() {
+            Ok(_guard) => {
+                self.remove(slot);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Delete an entry's clone and metadata; the caller must hold its lock
+    fn remove(&self, slot: &Arc<Slot>) {
+        if self.is_current(slot) {
+            self.entries.lock().unwrap().remove(&slot.key);
+        }
+        *slot.info.lock().unwrap() = None;
+        let _ = std::fs::remove_file(self.meta_path(&slot.key));
+        let _ = std::fs::remove_dir_all(self.dir.join(&slot.key));
+    }
+}
+
+/// Stable directory name for a URL and clone strategy
+fn cache_key(url: &str, strategy: CloneStrategy) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(url.as_bytes());
+    if let Some(filter) = strategy.filter() {
+        hasher.update(b"\n");
+        hasher.update(filter.as_bytes());
+    }
+    hex::encode(&hasher.finalize()[..16])
+}
+
+fn directory_size(path: &Path) -> u64 {
+    WalkDir::new(path)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.metadata().ok())
+        .filter(|metadata| metadata.is_file())
+        .map(|metadata| metadata.len())
+        .sum()
+}
@@ -0,0 +1,289 @@
+use crate::models::{
+    get_language_color, DirectoryDelta, DirectoryNode, LanguageBreakdown, LanguageDelta,
+    RepoAnalysis, RepoComparison,
+};
+use crate::summary::repo_ref;
+use std::collections::{BTreeMap, HashMap};
+
+/// Compare two analyses, usually of one repository at different times.
+///
+/// Directories are matched by path and compared by their totals including
+/// subdirectories. Ages grow with elapsed time alone, so they are reported
+/// but do not make a directory count as changed.
+pub fn compare(base: &RepoAnalysis, head: &RepoAnalysis) -> RepoComparison {
+    let base_dirs = directories_by_path(&base.directories);
+    let head_dirs = directories_by_path(&head.directories);
+
+    let mut added = Vec::new();
+    let mut changed = Vec::new();
+    let mut unchanged = 0;
+    for (path, head_dir) in &head_dirs {
+        match base_dirs.get(path) {
+            None => added.push(directory_delta(None, Some(head_dir))),
+            Some(base_dir) => {
+                let delta = directory_delta(Some(base_dir), Some(head_dir));
+                if delta.lines_delta != 0 || delta.languages.iter().any(|l| l.lines_delta != 0) {
+                    changed.push(delta);
+                } else {
+                    unchanged += 1;
+                }
+            }
+        }
+    }
+    let mut removed: Vec<DirectoryDelta> = base_dirs
+        .iter()
+        .filter(|(path, _)| !head_dirs.contains_key(*path))
+        .map(|(_, base_dir)| directory_delta(Some(base_dir), None))
+        .collect();
+
+    for deltas in [&mut added, &mut removed, &mut changed] {
+        deltas.sort_by(|a, b| {
+            b.lines_delta
+                .unsigned_abs()
+                .cmp(&a.lines_delta.unsigned_abs())
+                .then_with(|| a.path.cmp(&b.path))
+        });
+    }
+
+    RepoComparison {
+        base: repo_ref(base),
+        head: repo_ref(head),
+        lines_delta: delta(base.total_lines, head.total_lines),
+        age_delta: delta(base.age_days, head.age_days),
+        languages: language_deltas(&base.languages, &head.languages),
+        added,
+        removed,
+        changed,
+        unchanged,
+    }
+}
+
+fn delta(base: u64, head: u64) -> i64 {
+    head as i64 - base as i64
+}
+
+fn directories_by_path(nodes: &[DirectoryNode]) -> HashMap<&str, &DirectoryNode> {
+    fn add<'a>(nodes: &'a [DirectoryNode], map: &mut HashMap<&'a str, &'a DirectoryNode>) {
+        for node in nodes {
+            map.insert(&node.path, node);
+            add(&node.children, map);
+        }
+    }
+
+    let mut map = HashMap::new();
+    add(nodes, &mut map);
+    map
+}
+
+/// Delta of a directory in either or both analyses
+fn directory_delta(base: Option<&DirectoryNode>, head: Option<&DirectoryNode>) -> DirectoryDelta {
+    let node = head.or(base).expect("directory is in one of the analyses");
+    let base_lines = base.map_or(0, |dir| dir.total_lines);
+    let head_lines = head.map_or(0, |dir| dir.total_lines);
+
+    DirectoryDelta {
+        path: node.path.clone(),
+        name: node.name.clone(),
+        base_lines,
+        head_lines,
+        lines_delta: delta(base_lines, head_lines),
+        base_age_days: base.map(|dir| dir.age_days),
+        head_age_days: head.map(|dir| dir.age_days),
+        languages: language_deltas(
+            base.map_or(&[], |dir| &dir.total_languages),
+            head.map_or(&[], |dir| &dir.total_languages),
+        ),
+    }
+}
+
+/// Per-language changes, largest first
+fn language_deltas(base: &[LanguageBreakdown], head: &[LanguageBreakdown]) -> Vec<LanguageDelta> {
+    let mut languages: BTreeMap<&str, (Option<&LanguageBreakdown>, Option<&LanguageBreakdown>)> =
+        BTreeMap::new();
+    for language in base {
+        languages.entry(&language.language).or_default().0 = Some(language);
+    }
+    for language in head {
+        languages.entry(&language.language).or_default().1 = Some(language);
+    }
+
+    let mut deltas: Vec<LanguageDelta> = languages
+        .into_iter()
+        .map(|(language, (base, head))| {
+            let base_lines = base.map_or(0, |l| l.lines);
+            let head_lines = head.map_or(0, |l| l.lines);
+            LanguageDelta {
+                language: language.to_string(),
+                color: get_language_color(language).to_string(),
+                base_lines,
+                head_lines,
+                lines_delta: delta(base_lines, head_lines),
+                percentage_delta: head.map_or(0.0, |l| l.percentage)
+                    - base.map_or(0.0, |l| l.percentage),
+            }
+        })
+        .collect();
+    // Stable sort keeps languages with equal changes in name order
+    deltas.sort_by_key(|l| std::cmp::Reverse(l.lines_delta.unsigned_abs()));
+    deltas
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::{build_directory_tree, calculate_language_breakdown, DirStats, TreeOptions};
+    use crate::models::RepoStatus;
+    use tokio_util::sync::CancellationToken;
+
+    /// Analysis of top-level directories given as (path, language, lines, age)
+    fn analysis(id: &str, age_days: u64, dirs: &[(&str, &str, u64, u64)]) -> RepoAnalysis {
+        let mut dir_stats = HashMap::new();
+        let mut language_stats = HashMap::new();
+        for (path, language, lines, _) in dirs {
+            let stats = DirStats {
+                lines: *lines,
+                languages: HashMap::from([(language.to_string(), *lines)]),
+            };
+            dir_stats.insert(path.to_string(), stats);
+            *language_stats.entry(language.to_string()).or_insert(0) += lines;
+        }
+        let age = |path: &str| dirs.iter().find(|d| d.0 == path).map_or(0, |d| d.3);
+        let directories = build_directory_tree(
+            &dir_stats,
+            age,
+            TreeOptions::default(),
+            &CancellationToken::new(),
+        )
+        .unwrap();
+        let total_lines = dirs.iter().map(|d| d.2).sum();
+
+        RepoAnalysis {
+            id: id.to_string(),
+            name: "city".to_string(),
+            display_name: None,
+            path: "/src/city".to_string(),
+            age_days,
+            total_lines,
+            languages: calculate_language_breakdown(&language_stats, total_lines),
+            directories,
+            status: RepoStatus::Ok,
+            parent_id: None,
+            remote: None,
+            estimated: false,
+            clone_strategy: None,
+            approximate_directory_ages: false,
+            truncated: false,
+        }
+    }
+
+    fn comparison() -> RepoComparison {
+        let base = analysis(
+            "base",
+            30,
+            &[
+                ("src", "Rust", 100, 10),
+                ("docs", "JavaScript", 20, 30),
+                ("lib", "Rust", 10, 5),
+                ("old", "Python", 10, 20),
+            ],
+        );
+        let head = analysis(
+            "head",
+            60,
+            &[
+                ("src", "Rust", 120, 40),
+                ("docs", "JavaScript", 20, 60),
+                ("lib", "Python", 10, 35),
+                ("new", "Go", 5, 1),
+            ],
+        );
+        compare(&base, &head)
+    }
+
+    fn paths(deltas: &[DirectoryDelta]) -> Vec<&str> {
+        deltas.iter().map(|d| d.path.as_str()).collect()
+    }
+
+    #[test]
+    fn reports_repository_deltas() {
+        let comparison = comparison();
+        assert_eq!(comparison.base.id, "base");
+        assert_eq!(comparison.head.id, "head");
+        assert_eq!(comparison.lines_delta, 15);
+        assert_eq!(comparison.age_delta, 30);
+    }
+
+    #[test]
+    fn reports_added_and_removed_directories() {
+        let comparison = comparison();
+
+        assert_eq!(paths(&comparison.added), ["new"]);
+        let added = &comparison.added[0];
+        assert_eq!(
+            (added.base_lines, added.head_lines, added.lines_delta),
+            (0, 5, 5)
+        );
+        assert_eq!((added.base_age_days, added.head_age_days), (None, Some(1)));
+
+        assert_eq!(paths(&comparison.removed), ["old"]);
+        let removed = &comparison.removed[0];
+        assert_eq!(removed.lines_delta, -10);
+        assert_eq!(
+            (removed.base_age_days, removed.head_age_days),
+            (Some(20), None)
+        );
+    }
+
+    #[test]
+    fn changed_directories_differ_in_lines_or_languages() {
+        let comparison = comparison();
+
+        // `lib` kept its size but moved from Rust to Python
+        assert_eq!(paths(&comparison.changed), ["src", "lib"]);
+        let src = &comparison.changed[0];
+        assert_eq!(
+            (src.base_lines, src.head_lines, src.lines_delta),
+            (100, 120, 20)
+        );
+        assert_eq!((src.base_age_days, src.head_age_days), (Some(10), Some(40)));
+        let lib = &comparison.changed[1];
+        assert_eq!(lib.lines_delta, 0);
+        let languages: Vec<(&str, i64)> = lib
+            .languages
+            .iter()
+            .map(|l| (l.language.as_str(), l.lines_delta))
+            .collect();
+        assert_eq!(languages, [("Python", 10), ("Rust", -10)]);
+
+        // `docs` only grew older
+        assert_eq!(comparison.unchanged, 1);
+    }
+
+    #[test]
+    fn reports_language_deltas_largest_first() {
+        let comparison = comparison();
+        let languages: Vec<(&str, u64, u64, i64)> = comparison
+            .languages
+            .iter()
+            .map(|l| {
+                (
+                    l.language.as_str(),
+                    l.base_lines,
+                    l.head_lines,
+                    l.lines_delta,
+                )
+            })
+            .collect();
+        assert_eq!(
+            languages,
+            [
+                ("Rust", 110, 120, 10),
+                ("Go", 0, 5, 5),
+                ("JavaScript", 20, 20, 0),
+                ("Python", 10, 10, 0),
+            ]
+        );
+        let go = &comparison.languages[1];
+        assert!(go.percentage_delta > 0.0);
+    }
+}
@@ -0,0 +1,62 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// Server configuration read from `CODECITY_*` environment variables
+#[derive(Clone)]
+pub struct Config {
+    /// Maximum number of repositories analyzed at the same time
+    pub max_concurrent_repos: usize,
+    /// Number of threads used to read and count files
+    pub file_threads: usize,
+    /// Maximum number of repositories waiting for or undergoing analysis
+    pub max_queued_repos: usize,
+    /// Token used for GitHub API calls and clones unless a request supplies its own
+    pub github_token: Option<String>,
+    /// GitHub API base URL, e.g. `https://github.example.com/api/v3` for GitHub Enterprise
+    pub github_api_url: Option<String>,
+    /// Token used for GitLab API calls and clones unless a request supplies its own
+    pub gitlab_token: Option<String>,
+    /// GitLab instance URL, `https://gitlab.com` unless set
+    pub gitlab_url: String,
+    /// Shared secret that push webhooks are signed with; webhooks are refused without it
+    pub webhook_secret: Option<String>,
+    /// Directory holding cached clones of remote repositories
+    pub cache_dir: PathBuf,
+    /// Size budget for cached clones; least recently used clones are evicted beyond it
+    pub cache_max_bytes: u64,
+}
+
+impl Config {
+    pub fn from_env() -> Self {
+        let cpus = num_cpus::get();
+        Self {
+            max_concurrent_repos: env_or("CODECITY_MAX_CONCURRENT_REPOS", (cpus / 2).max(1)),
+            file_threads: env_or("CODECITY_FILE_THREADS", cpus),
+            max_queued_repos: env_or("CODECITY_MAX_QUEUED_REPOS", 1024),
+            github_token: env_opt("CODECITY_GITHUB_TOKEN").or_else(|| env_opt("GITHUB_TOKEN")),
+            github_api_url: env_opt("CODECITY_GITHUB_API_URL"),
+            gitlab_token: env_opt("CODECITY_GITLAB_TOKEN").or_else(|| env_opt("GITLAB_TOKEN")),
+            gitlab_url: env_opt("CODECITY_GITLAB_URL")
+                .unwrap_or_else(|| "https://gitlab.com".to_string()),
+            webhook_secret: env_opt("CODECITY_WEBHOOK_SECRET"),
+            cache_dir: env_opt("CODECITY_CACHE_DIR")
+                .map(PathBuf::from)
+                .unwrap_or_else(|| std::env::temp_dir().join("codecity-cache")),
+            cache_max_bytes: env_or("CODECITY_CACHE_MAX_BYTES", 10 * 1024 * 1024 * 1024),
+        }
+    }
+}
+
+fn env_or<T: FromStr>(name: &str, default: T) -> T {
+    match std::env::var(name) {
+        Ok(value) => value.parse().unwrap_or_else(|_| {
+            tracing::warn!("Ignoring invalid value {:?} for {}", value, name);
+            default
+        }),
+        Err(_) => default,
+    }
+}
+
+fn env_opt(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.is_empty())
+}
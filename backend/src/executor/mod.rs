@@ -0,0 +1,146 @@
+use crate::config::Config;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use thiserror::Error;
+use tokio::sync::Semaphore;
+use tokio::task::JoinHandle;
+
+#[derive(Error, Debug)]
+pub enum ExecutorError {
+    #[error(
+        "Analysis queue is full ({queued} of {capacity} repositories pending), try again later"
+    )]
+    QueueFull { queued: usize, capacity: usize },
+    #[error("Analysis task failed: {0}")]
+    Task(#[from] tokio::task::JoinError),
+}
+
+/// Bounded executor for repository analysis.
+///
+/// Repository analyses run on blocking threads limited by a semaphore, and file
+/// reading within an analysis uses a dedicated rayon pool, so scans never take
+/// over the global rayon pool or every core of the server. Each request
+/// acquires permits one repository at a time from a FIFO semaphore, which
+/// interleaves repositories from concurrent requests instead of running them
+/// one request after another.
+#[derive(Clone)]
+pub struct AnalysisExecutor {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    repo_permits: Arc<Semaphore>,
+    file_pool: rayon::ThreadPool,
+    queued: AtomicUsize,
+    max_queued: usize,
+}
+
+/// Queue space held by a request until all of its repositories are analyzed
+pub struct QueueReservation {
+    inner: Arc<Inner>,
+    count: usize,
+}
+
+impl Drop for QueueReservation {
+    fn drop(&mut self) {
+        self.inner.queued.fetch_sub(self.count, Ordering::SeqCst);
+    }
+}
+
+impl AnalysisExecutor {
+    pub fn new(config: &Config) -> Self {
+        let file_pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(config.file_threads.max(1))
+            .thread_name(|i| format!("codecity-files-{}", i))
+            .build()
+            .expect("Failed to build file analysis thread pool");
+
+        tracing::info!(
+            "Analysis executor: {} concurrent repositories, {} file threads, queue of {}",
+            config.max_concurrent_repos,
+            config.file_threads,
+            config.max_queued_repos
+        );
+
+        Self {
+            inner: Arc::new(Inner {
+                repo_permits: Arc::new(Semaphore::new(config.max_concurrent_repos.max(1))),
+                file_pool,
+                queued: AtomicUsize::new(0),
+                max_queued: config.max_queued_repos,
+            }),
+        }
+    }
+
+    /// Reserve queue space for `count` repositories, failing fast when the queue is full
+    pub fn reserve(&self, count: usize) -> Result<QueueReservation, ExecutorError> {
+        let inner = &self.inner;
+        inner
+            .queued
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |queued| {
+                (queued + count <= inner.max_queued).then_some(queued + count)
+            })
+            .map_err(|queued| ExecutorError::QueueFull {
+                queued,
+                capacity: inner.max_queued,
+            })?;
+
+        Ok(QueueReservation {
+            inner: self.inner.clone(),
+            count,
+        })
+    }
+
+    /// Run a single blocking analysis job
+    pub async fn run<T, F>(&self, job: F) -> Result<T, ExecutorError>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        let _reservation = self.reserve(1)?;
+        Ok(self.start(job).await.await?)
+    }
+
+    /// Run a blocking analysis job for each item, returning results in input order
+    pub async fn map<I, T, F>(&self, items: Vec<I>, job: F) -> Result<Vec<T>, ExecutorError>
+    where
+        I: Send + 'static,
+        T: Send + 'static,
+        F: Fn(I) -> T + Send + Sync + 'static,
+    {
+        let _reservation = self.reserve(items.len())?;
+        let job = Arc::new(job);
+
+        let mut handles = Vec::with_capacity(items.len());
+        for item in items {
+            let job = job.clone();
+            handles.push(self.start(move || job(item)).await);
+        }
+
+        let mut results = Vec::with_capacity(handles.len());
+        for handle in handles {
+            results.push(handle.await?);
+        }
+        Ok(results)
+    }
+
+    /// Wait for a repository permit, then start the job on a blocking thread
+    async fn start<T, F>(&self, job: F) -> JoinHandle<T>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        let permit = self
+            .inner
+            .repo_permits
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("Analysis semaphore is never closed");
+        let inner = self.inner.clone();
+        tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            inner.file_pool.install(job)
+        })
+    }
+}
@@ -1,15 +1,19 @@
+use crate::executor::{AnalysisExecutor, ExecutorError};
 use crate::models::{
-    get_language_color, get_language_from_extension, DirectoryNode, LanguageBreakdown, RepoAnalysis,
+    get_language_color, get_language_from_extension, DirectoryNode, FailureKind, LanguageBreakdown,
+    RepoAnalysis, RepoStatus, ScanDirectoryRequest, ScanFailure,
 };
 use chrono::Utc;
-use git2::Repository;
+use git2::{ErrorClass, ErrorCode, Repository};
+use globset::{Glob, GlobSet, GlobSetBuilder};
 use rayon::prelude::*;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::Path;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use thiserror::Error;
+use tokio_util::sync::CancellationToken;
 use uuid::Uuid;
 use walkdir::WalkDir;
 
@@ -23,10 +27,54 @@ pub enum GitError {
     NoCommits,
     #[error("Invalid repository path")]
     InvalidPath,
+    #[error("Invalid scan pattern: {0}")]
+    InvalidPattern(#[from] globset::Error),
+    #[error(transparent)]
+    Executor(#[from] ExecutorError),
+    #[error("Analysis was cancelled")]
+    Cancelled,
 }
 
-pub fn analyze_repository(path: &str) -> Result<RepoAnalysis, GitError> {
-    let repo_path = Path::new(path);
+impl GitError {
+    /// Classify the error for reporting scan failures
+    pub fn kind(&self) -> FailureKind {
+        match self {
+            GitError::OpenRepo(e) => match (e.code(), e.class()) {
+                (ErrorCode::UnbornBranch, _) => FailureKind::Empty,
+                (_, ErrorClass::Repository) => FailureKind::NotARepository,
+                (
+                    _,
+                    ErrorClass::Odb
+                    | ErrorClass::Object
+                    | ErrorClass::Zlib
+                    | ErrorClass::Index
+                    | ErrorClass::Tree
+                    | ErrorClass::Reference,
+                ) => FailureKind::Corrupt,
+                (_, ErrorClass::Os | ErrorClass::Filesystem) => FailureKind::Unreadable,
+                _ => FailureKind::Other,
+            },
+            GitError::Io(_) => FailureKind::Unreadable,
+            GitError::NoCommits => FailureKind::Empty,
+            GitError::InvalidPath => FailureKind::InvalidPath,
+            GitError::InvalidPattern(_) | GitError::Executor(_) | GitError::Cancelled => {
+                FailureKind::Other
+            }
+        }
+    }
+}
+
+/// Analyze a repository checkout. Submodules are counted as part of it unless
+/// `separate_submodules` is set because they are analyzed on their own.
+pub fn analyze_repository(
+    repo_path: &Path,
+    separate_submodules: bool,
+    cancel: &CancellationToken,
+) -> Result<RepoAnalysis, GitError> {
+    if cancel.is_cancelled() {
+        return Err(GitError::Cancelled);
+    }
+
     if !repo_path.exists() {
         return Err(GitError::InvalidPath);
     }
@@ -37,11 +85,17 @@ pub fn analyze_repository(path: &str) -> Result<RepoAnalysis, GitError> {
         .map(|n| n.to_string_lossy().to_string())
         .unwrap_or_else(|| "unknown".to_string());
 
-    // Get repository age from first commit
-    let age_days = get_repo_age_days(&repo)?;
+    // Get repository age from first commit; repos without commits on HEAD
+    // are still analyzed from the working tree
+    let status = get_head_status(&repo)?;
+    let age_days = match status {
+        RepoStatus::Ok | RepoStatus::Detached => get_repo_age_days(&repo)?,
+        RepoStatus::Unborn | RepoStatus::Empty => 0,
+    };
 
     // Analyze files
-    let (total_lines, language_stats, directories) = analyze_files(repo_path)?;
+    let (total_lines, language_stats, directories) =
+        analyze_files(repo_path, separate_submodules, cancel)?;
 
     // Convert language stats to breakdown
     let languages = calculate_language_breakdown(&language_stats, total_lines);
@@ -49,14 +103,38 @@ pub fn analyze_repository(path: &str) -> Result<RepoAnalysis, GitError> {
     Ok(RepoAnalysis {
         id: Uuid::new_v4().to_string(),
         name: repo_name,
-        path: path.to_string(),
+        path: repo_path.to_string_lossy().to_string(),
         age_days,
         total_lines,
         languages,
         directories,
+        status,
+        parent_id: None,
+        remote: None,
+        estimated: false,
+        display_name: None,
+        clone_strategy: None,
+        approximate_directory_ages: false,
+        truncated: false,
     })
 }
 
+fn get_head_status(repo: &Repository) -> Result<RepoStatus, GitError> {
+    match repo.head() {
+        Ok(_) if repo.head_detached()? => Ok(RepoStatus::Detached),
+        Ok(_) => Ok(RepoStatus::Ok),
+        Err(e) if e.code() == ErrorCode::UnbornBranch || e.code() == ErrorCode::NotFound => {
+            // Distinguish a freshly initialized repo from an orphan branch
+            if repo.references()?.next().is_none() {
+                Ok(RepoStatus::Empty)
+            } else {
+                Ok(RepoStatus::Unborn)
+            }
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
 fn get_repo_age_days(repo: &Repository) -> Result<u64, GitError> {
     let mut revwalk = repo.revwalk()?;
     revwalk.push_head()?;
@@ -75,76 +153,186 @@ fn get_repo_age_days(repo: &Repository) -> Result<u64, GitError> {
     Err(GitError::NoCommits)
 }
 
+type FileAnalysis = (u64, HashMap<String, u64>, Vec<DirectoryNode>);
+
 fn analyze_files(
     repo_path: &Path,
-) -> Result<(u64, HashMap<String, u64>, Vec<DirectoryNode>), GitError> {
+    separate_submodules: bool,
+    cancel: &CancellationToken,
+) -> Result<FileAnalysis, GitError> {
     let mut total_lines = 0u64;
     let mut language_stats: HashMap<String, u64> = HashMap::new();
     let mut dir_stats: HashMap<String, DirStats> = HashMap::new();
 
-    for entry in WalkDir::new(repo_path)
+    let files: Vec<(std::path::PathBuf, &'static str)> = WalkDir::new(repo_path)
         .into_iter()
-        .filter_entry(|e| !is_ignored(e.path(), repo_path))
+        .filter_entry(|e| {
+            !is_ignored(e.path(), repo_path) && !is_nested_repo(e, repo_path, separate_submodules)
+        })
+        .take_while(|_| !cancel.is_cancelled())
         .filter_map(|e| e.ok())
-    {
-        if entry.file_type().is_file() {
-            if let Some(ext) = entry.path().extension() {
-                if let Some(language) = get_language_from_extension(&ext.to_string_lossy()) {
-                    if let Ok(content) = fs::read_to_string(entry.path()) {
-                        let lines = content.lines().count() as u64;
-                        total_lines += lines;
-                        *language_stats.entry(language.to_string()).or_insert(0) += lines;
-
-                        // Track directory stats
-                        if let Some(parent) = entry.path().parent() {
-                            let rel_path = parent
-                                .strip_prefix(repo_path)
-                                .unwrap_or(parent)
-                                .to_string_lossy()
-                                .to_string();
-                            let dir_stat = dir_stats.entry(rel_path).or_insert_with(DirStats::new);
-                            dir_stat.lines += lines;
-                            *dir_stat.languages.entry(language.to_string()).or_insert(0) += lines;
-                        }
-                    }
-                }
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| {
+            let ext = entry.path().extension()?;
+            let language = get_language_from_extension(&ext.to_string_lossy())?;
+            Some((entry.into_path(), language))
+        })
+        .collect();
+
+    // Read and count files in parallel on the current rayon pool
+    let counted: Vec<(&std::path::PathBuf, &'static str, u64)> = files
+        .par_iter()
+        .filter_map(|(path, language)| {
+            if cancel.is_cancelled() {
+                return None;
             }
+            let content = fs::read_to_string(path).ok()?;
+            Some((path, *language, content.lines().count() as u64))
+        })
+        .collect();
+
+    if cancel.is_cancelled() {
+        return Err(GitError::Cancelled);
+    }
+
+    for (path, language, lines) in counted {
+        total_lines += lines;
+        *language_stats.entry(language.to_string()).or_insert(0) += lines;
+
+        // Track directory stats
+        if let Some(parent) = path.parent() {
+            let rel_path = parent
+                .strip_prefix(repo_path)
+                .unwrap_or(parent)
+                .to_string_lossy()
+                .to_string();
+            let dir_stat = dir_stats.entry(rel_path).or_default();
+            dir_stat.lines += lines;
+            *dir_stat.languages.entry(language.to_string()).or_insert(0) += lines;
         }
     }
 
     // Build directory tree
-    let directories = build_directory_tree(repo_path, &dir_stats)?;
+    // Keep the full hierarchy; chains are compressed when the tree is queried
+    let ages = get_directory_ages(repo_path, &with_ancestors(dir_stats.keys()), cancel)?;
+    let directories = build_directory_tree(
+        &dir_stats,
+        |path| ages.get(path).copied().unwrap_or(0),
+        TreeOptions::default(),
+        cancel,
+    )?;
 
     Ok((total_lines, language_stats, directories))
 }
 
+/// Lines of code directly inside a directory, keyed by its path relative to the repository
 #[derive(Default)]
-struct DirStats {
-    lines: u64,
-    languages: HashMap<String, u64>,
+pub struct DirStats {
+    pub lines: u64,
+    pub languages: HashMap<String, u64>,
+}
+
+/// Options controlling the shape of a built directory tree
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TreeOptions {
+    /// Collapse chains of directories without files of their own, see [`compress_chains`]
+    pub compress_chains: bool,
 }
 
-impl DirStats {
-    fn new() -> Self {
-        Self::default()
+impl TreeOptions {
+    /// Reshape a full directory hierarchy as the options ask
+    pub fn apply(self, nodes: Vec<DirectoryNode>) -> Vec<DirectoryNode> {
+        if self.compress_chains {
+            compress_chains(nodes)
+        } else {
+            nodes
+        }
     }
 }
 
-fn build_directory_tree(
-    repo_path: &Path,
+/// The given directory paths and the paths of all their ancestors
+pub(crate) fn with_ancestors<'a>(dirs: impl IntoIterator<Item = &'a String>) -> HashSet<String> {
+    let dirs: Vec<&String> = dirs.into_iter().collect();
+    let mut paths: HashSet<String> = dirs.iter().map(|path| path.to_string()).collect();
+    for path in dirs {
+        for ancestor in Path::new(path).ancestors().skip(1) {
+            let ancestor = ancestor.to_string_lossy();
+            if ancestor.is_empty() || !paths.insert(ancestor.to_string()) {
+                break;
+            }
+        }
+    }
+    paths
+}
+
+/// Nest directory nodes keyed by path below their parents, returning the
+/// top-level ones.
+///
+/// Every ancestor of a directory needs a node, see [`with_ancestors`]. Nodes
+/// are attached from the deepest directories up, and `finish` runs on each
+/// node once all of its children are attached, before it is attached itself.
+pub(crate) fn nest_directories<T>(
+    mut nodes: HashMap<String, T>,
+    children: impl Fn(&mut T) -> &mut Vec<T>,
+    mut finish: impl FnMut(&str, &mut T),
+) -> Vec<T> {
+    let mut ordered: Vec<String> = nodes.keys().cloned().collect();
+    ordered.sort_by_key(|path| std::cmp::Reverse(Path::new(path).components().count()));
+
+    let mut roots = Vec::new();
+    for path in &ordered {
+        let Some(mut node) = nodes.remove(path) else {
+            continue;
+        };
+        finish(path, &mut node);
+
+        let parent = Path::new(path)
+            .parent()
+            .map(|p| p.to_string_lossy().to_string())
+            .filter(|p| !p.is_empty())
+            .and_then(|p| nodes.get_mut(&p));
+        match parent {
+            Some(parent) => children(parent).push(node),
+            None => roots.push(node),
+        }
+    }
+    roots
+}
+
+/// Build the directory hierarchy, using `directory_age` for each directory's age.
+///
+/// Directories without files of their own get a node too, so every directory
+/// hangs below its parent. Files directly in the repository root form a
+/// separate "(root)" node alongside the top-level directories.
+pub fn build_directory_tree(
     dir_stats: &HashMap<String, DirStats>,
+    directory_age: impl Fn(&str) -> u64,
+    options: TreeOptions,
+    cancel: &CancellationToken,
 ) -> Result<Vec<DirectoryNode>, GitError> {
-    let mut root_dirs: Vec<DirectoryNode> = Vec::new();
+    let paths = with_ancestors(dir_stats.keys());
+
     let mut dir_map: HashMap<String, DirectoryNode> = HashMap::new();
+    let mut total_stats: HashMap<String, HashMap<String, u64>> = HashMap::new();
 
     // Create nodes for all directories
-    for (path, stats) in dir_stats {
+    for path in &paths {
+        if cancel.is_cancelled() {
+            return Err(GitError::Cancelled);
+        }
+
         let name = Path::new(path)
             .file_name()
             .map(|n| n.to_string_lossy().to_string())
             .unwrap_or_else(|| path.clone());
 
-        let languages = calculate_language_breakdown(&stats.languages, stats.lines);
+        let stats = dir_stats.get(path);
+        let lines = stats.map_or(0, |stats| stats.lines);
+        let own_languages = stats
+            .map(|stats| stats.languages.clone())
+            .unwrap_or_default();
+        let languages = calculate_language_breakdown(&own_languages, lines);
+        total_stats.insert(path.clone(), own_languages);
 
         let node = DirectoryNode {
             name: if name.is_empty() {
@@ -153,83 +341,125 @@ fn build_directory_tree(
                 name
             },
             path: path.clone(),
-            age_days: get_directory_age(repo_path, path).unwrap_or(0),
-            lines: stats.lines,
+            age_days: directory_age(path),
+            lines,
             languages,
+            total_lines: 0,
+            total_languages: Vec::new(),
             children: Vec::new(),
+            child_count: None,
+            folded: None,
         };
 
         dir_map.insert(path.clone(), node);
     }
 
-    // Build hierarchy
-    let paths: Vec<String> = dir_map.keys().cloned().collect();
-    for path in &paths {
-        if path.is_empty() {
-            continue;
-        }
-        let parent_path = Path::new(path)
-            .parent()
-            .map(|p| p.to_string_lossy().to_string())
-            .unwrap_or_default();
+    let mut root_dirs = nest_directories(
+        dir_map,
+        |node| &mut node.children,
+        |path, node| {
+            let languages = total_stats.remove(path).unwrap_or_default();
+            sort_directories(&mut node.children);
+            node.total_lines =
+                node.lines + node.children.iter().map(|c| c.total_lines).sum::<u64>();
+            node.total_languages = calculate_language_breakdown(&languages, node.total_lines);
 
-        if let Some(node) = dir_map.remove(path) {
-            if parent_path.is_empty() || !dir_map.contains_key(&parent_path) {
-                root_dirs.push(node);
-            } else if let Some(parent) = dir_map.get_mut(&parent_path) {
-                parent.children.push(node);
-            } else {
-                root_dirs.push(node);
+            // Roll this directory's languages up into its parent's totals
+            let parent_stats = Path::new(path)
+                .parent()
+                .map(|p| p.to_string_lossy().to_string())
+                .filter(|p| !p.is_empty())
+                .and_then(|p| total_stats.get_mut(&p));
+            if let Some(parent_stats) = parent_stats {
+                for (language, lines) in languages {
+                    *parent_stats.entry(language).or_insert(0) += lines;
+                }
             }
-        }
-    }
-
-    // Add root directory if it exists
-    if let Some(root) = dir_map.remove("") {
-        root_dirs.insert(0, root);
-    }
+        },
+    );
+    sort_directories(&mut root_dirs);
+    Ok(options.apply(root_dirs))
+}
 
-    // Sort by lines of code
-    root_dirs.sort_by(|a, b| b.lines.cmp(&a.lines));
+/// Collapse each chain of directories that have no files of their own and a
+/// single subdirectory, such as `src/main/java`, into one node.
+///
+/// The node is named after the whole chain and takes the path of its last
+/// directory, so its children keep their paths.
+pub fn compress_chains(nodes: Vec<DirectoryNode>) -> Vec<DirectoryNode> {
+    nodes
+        .into_iter()
+        .map(|mut node| {
+            while node.lines == 0 && node.children.len() == 1 {
+                let child = node.children.pop().expect("node has one child");
+                node = DirectoryNode {
+                    name: format!("{}/{}", node.name, child.name),
+                    age_days: node.age_days.max(child.age_days),
+                    ..child
+                };
+            }
+            node.children = compress_chains(node.children);
+            node
+        })
+        .collect()
+}
 
-    Ok(root_dirs)
+/// Order directories largest first, by lines including subdirectories, then by path
+pub fn sort_directories(nodes: &mut [DirectoryNode]) {
+    nodes.sort_by(|a, b| {
+        b.total_lines
+            .cmp(&a.total_lines)
+            .then_with(|| a.path.cmp(&b.path))
+    });
 }
 
-fn get_directory_age(repo_path: &Path, dir_path: &str) -> Result<u64, GitError> {
+/// Age in days of each of `paths`, taken from the oldest commit whose tree
+/// contains the directory.
+///
+/// History is walked once, oldest first, until every directory has been seen.
+/// Directories missing from history, and all of them in repositories without
+/// commits, are left out.
+fn get_directory_ages(
+    repo_path: &Path,
+    paths: &HashSet<String>,
+    cancel: &CancellationToken,
+) -> Result<HashMap<String, u64>, GitError> {
+    let mut ages = HashMap::new();
     let repo = Repository::open(repo_path)?;
-    let full_path = if dir_path.is_empty() {
-        repo_path.to_path_buf()
-    } else {
-        repo_path.join(dir_path)
-    };
-
-    // Find oldest commit that touches this directory
     let mut revwalk = repo.revwalk()?;
-    revwalk.push_head()?;
+    if revwalk.push_head().is_err() {
+        return Ok(ages);
+    }
     revwalk.set_sorting(git2::Sort::TIME | git2::Sort::REVERSE)?;
 
+    let now = Utc::now().timestamp();
+    let mut unseen: Vec<&String> = paths.iter().collect();
     for oid_result in revwalk {
-        let oid = oid_result?;
-        let commit = repo.find_commit(oid)?;
-
-        // Check if this commit touches the directory
-        if let Ok(tree) = commit.tree() {
-            let rel_path = full_path
-                .strip_prefix(repo_path)
-                .unwrap_or(full_path.as_path());
-            if rel_path.as_os_str().is_empty() || tree.get_path(rel_path).is_ok() {
-                let commit_time = commit.time().seconds();
-                let now = Utc::now().timestamp();
-                let age_seconds = now - commit_time;
-                return Ok((age_seconds / 86400) as u64);
-            }
+        if unseen.is_empty() {
+            break;
         }
+        if cancel.is_cancelled() {
+            return Err(GitError::Cancelled);
+        }
+        let commit = repo.find_commit(oid_result?)?;
+        // Treeless clones leave out the trees of older commits
+        let Ok(tree) = commit.tree() else {
+            continue;
+        };
+        let age_days = ((now - commit.time().seconds()) / 86400) as u64;
+        unseen.retain(|path| {
+            let seen = path.is_empty() || tree.get_path(Path::new(path.as_str())).is_ok();
+            if seen {
+                ages.insert(path.to_string(), age_days);
+            }
+            !seen
+        });
     }
 
-    Ok(0)
+    Ok(ages)
 }
 
-fn calculate_language_breakdown(
+pub fn calculate_language_breakdown(
     stats: &HashMap<String, u64>,
     total: u64,
 ) -> Vec<LanguageBreakdown> {
@@ -247,11 +477,11 @@ fn calculate_language_breakdown(
         })
         .collect();
 
-    breakdown.sort_by(|a, b| b.lines.cmp(&a.lines));
+    breakdown.sort_by_key(|b| std::cmp::Reverse(b.lines));
     breakdown
 }
 
-fn is_ignored(path: &Path, repo_path: &Path) -> bool {
+pub fn is_ignored(path: &Path, repo_path: &Path) -> bool {
     let rel_path = path.strip_prefix(repo_path).unwrap_or(path);
     let path_str = rel_path.to_string_lossy();
 
@@ -291,33 +521,141 @@ fn is_ignored(path: &Path, repo_path: &Path) -> bool {
     false
 }
 
+/// Nested repositories are analyzed on their own, not as part of their parent.
+/// Submodules only are when `separate_submodules` is set; otherwise they count
+/// towards their parent.
+fn is_nested_repo(entry: &walkdir::DirEntry, repo_path: &Path, separate_submodules: bool) -> bool {
+    if !entry.file_type().is_dir() || entry.path() == repo_path {
+        return false;
+    }
+    match classify_git_dir(entry.path()) {
+        Some(GitDirKind::Submodule) => separate_submodules,
+        Some(_) => true,
+        None => false,
+    }
+}
+
+/// Directories never descended into when scanning for repositories
+const SKIP_DIRS: [&str; 7] = [
+    "node_modules",
+    "target",
+    ".cargo",
+    "vendor",
+    ".venv",
+    "venv",
+    "__pycache__",
+];
+
+/// Options controlling which repositories a directory scan picks up
+#[derive(Debug, Clone, Default)]
+pub struct ScanOptions {
+    /// Analyze submodules as separate repositories linked to their parent
+    /// instead of counting them towards it
+    pub include_submodules: bool,
+    /// Maximum directory depth below the scan root to look for repositories
+    pub max_depth: Option<usize>,
+    /// Only repositories whose path relative to the scan root matches are analyzed
+    pub include: Option<GlobSet>,
+    /// Directories whose path relative to the scan root matches are not descended into
+    pub exclude: Option<GlobSet>,
+    pub follow_symlinks: bool,
+    /// Maximum number of repositories to analyze
+    pub max_repos: Option<usize>,
+}
+
+impl ScanOptions {
+    pub fn from_request(request: &ScanDirectoryRequest) -> Result<Self, GitError> {
+        Ok(Self {
+            include_submodules: request.include_submodules,
+            max_depth: request.max_depth,
+            include: build_glob_set(&request.include)?,
+            exclude: build_glob_set(&request.exclude)?,
+            follow_symlinks: request.follow_symlinks,
+            max_repos: request.max_repos,
+        })
+    }
+}
+
+fn build_glob_set(patterns: &[String]) -> Result<Option<GlobSet>, GitError> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern)?);
+    }
+    Ok(Some(builder.build()?))
+}
+
+/// A repository discovered while scanning a directory
+#[derive(Debug, Clone)]
+pub struct DiscoveredRepo {
+    pub path: String,
+    /// Path of the enclosing repository when this is a submodule
+    pub parent: Option<String>,
+}
+
+enum GitDirKind {
+    Repository,
+    Submodule,
+    /// Linked worktree, identified by the common git directory it shares with its main repo
+    Worktree(std::path::PathBuf),
+}
+
+/// Classify a directory containing `.git`, which is a directory for regular repos
+/// and a `gitdir:` file for submodules and linked worktrees
+fn classify_git_dir(path: &Path) -> Option<GitDirKind> {
+    let git_path = path.join(".git");
+    let metadata = fs::symlink_metadata(&git_path).ok()?;
+    if metadata.is_dir() {
+        return Some(GitDirKind::Repository);
+    }
+    if !metadata.is_file() {
+        return None;
+    }
+
+    match Repository::open(path) {
+        Ok(repo) if repo.is_worktree() => {
+            let common = fs::canonicalize(repo.commondir())
+                .unwrap_or_else(|_| repo.commondir().to_path_buf());
+            Some(GitDirKind::Worktree(common))
+        }
+        Ok(_) => Some(GitDirKind::Submodule),
+        // Let analysis report why the repository cannot be opened
+        Err(_) => Some(GitDirKind::Repository),
+    }
+}
+
 /// Find all git repositories in subdirectories of the given path
-pub fn find_repositories(base_path: &str) -> Result<Vec<String>, GitError> {
+pub fn find_repositories(
+    base_path: &str,
+    options: &ScanOptions,
+    cancel: &CancellationToken,
+) -> Result<Vec<DiscoveredRepo>, GitError> {
     let base = Path::new(base_path);
     if !base.exists() {
         return Err(GitError::InvalidPath);
     }
 
-    let mut repos = Vec::new();
+    let mut repos: Vec<DiscoveredRepo> = Vec::new();
+    let mut submodules: Vec<String> = Vec::new();
+    let mut worktrees: Vec<(String, std::path::PathBuf)> = Vec::new();
 
-    // Directories to skip when scanning
-    let skip_dirs = [
-        "node_modules",
-        "target",
-        ".cargo",
-        "vendor",
-        ".venv",
-        "venv",
-        "__pycache__",
-    ];
-
-    for entry in WalkDir::new(base)
+    // Sorted so that `max_repos` keeps the same repositories on every scan
+    let mut walker = WalkDir::new(base)
         .min_depth(1)
+        .follow_links(options.follow_symlinks)
+        .sort_by_file_name();
+    if let Some(max_depth) = options.max_depth {
+        walker = walker.max_depth(max_depth);
+    }
+
+    for entry in walker
         .into_iter()
         .filter_entry(|e| {
             // Skip common non-repo directories for performance
             if let Some(name) = e.file_name().to_str() {
-                if skip_dirs.contains(&name) {
+                if SKIP_DIRS.contains(&name) {
                     return false;
                 }
                 // Skip hidden directories (except we need to find .git)
@@ -325,67 +663,414 @@ pub fn find_repositories(base_path: &str) -> Result<Vec<String>, GitError> {
                     return false;
                 }
             }
+            if let Some(exclude) = &options.exclude {
+                if exclude.is_match(e.path().strip_prefix(base).unwrap_or(e.path())) {
+                    return false;
+                }
+            }
             true
         })
         .filter_map(|e| e.ok())
     {
-        // Check if this directory contains a .git folder
-        if entry.file_type().is_dir() {
-            let git_dir = entry.path().join(".git");
-            if git_dir.exists() && git_dir.is_dir() {
-                if let Some(path_str) = entry.path().to_str() {
-                    repos.push(path_str.to_string());
-                }
+        if cancel.is_cancelled() {
+            return Err(GitError::Cancelled);
+        }
+
+        // Check if this directory contains a .git folder or gitdir file
+        if !entry.file_type().is_dir() {
+            continue;
+        }
+        if let Some(include) = &options.include {
+            if !include.is_match(entry.path().strip_prefix(base).unwrap_or(entry.path())) {
+                continue;
             }
         }
+        let Some(path_str) = entry.path().to_str() else {
+            continue;
+        };
+        match classify_git_dir(entry.path()) {
+            Some(GitDirKind::Repository) => repos.push(DiscoveredRepo {
+                path: path_str.to_string(),
+                parent: None,
+            }),
+            Some(GitDirKind::Submodule) => submodules.push(path_str.to_string()),
+            Some(GitDirKind::Worktree(common_dir)) => {
+                worktrees.push((path_str.to_string(), common_dir))
+            }
+            None => {}
+        }
+    }
+
+    // Following symlinks can reach the same repository through several paths
+    let mut seen_git_dirs: Vec<std::path::PathBuf> = Vec::new();
+    repos.retain(
+        |r| match fs::canonicalize(Path::new(&r.path).join(".git")) {
+            Ok(git_dir) if seen_git_dirs.contains(&git_dir) => false,
+            Ok(git_dir) => {
+                seen_git_dirs.push(git_dir);
+                true
+            }
+            Err(_) => true,
+        },
+    );
+
+    // Worktrees share history with their main repo, so only analyze one
+    // checkout per repository
+    for (path, common_dir) in worktrees {
+        if seen_git_dirs.contains(&common_dir) {
+            tracing::debug!(
+                "Skipping worktree {} of an already scanned repository",
+                path
+            );
+            continue;
+        }
+        seen_git_dirs.push(common_dir);
+        repos.push(DiscoveredRepo { path, parent: None });
+    }
+
+    if options.include_submodules {
+        for path in submodules {
+            // The parent is the closest enclosing repository found by the scan
+            let parent = repos
+                .iter()
+                .map(|r| r.path.as_str())
+                .chain(std::iter::once(base_path))
+                .filter(|candidate| {
+                    Path::new(&path).starts_with(candidate)
+                        && Path::new(candidate).join(".git").exists()
+                        && *candidate != path
+                })
+                .max_by_key(|candidate| candidate.len())
+                .map(|candidate| candidate.to_string());
+            repos.push(DiscoveredRepo { path, parent });
+        }
     }
 
     Ok(repos)
 }
 
-/// Analyze all repositories found in subdirectories (parallel)
-pub fn analyze_directory(base_path: &str) -> Result<Vec<RepoAnalysis>, GitError> {
-    let repo_paths = find_repositories(base_path)?;
-    let total = repo_paths.len();
+/// Result of analyzing every repository below a directory
+pub struct DirectoryAnalysis {
+    pub repos: Vec<RepoAnalysis>,
+    pub failures: Vec<ScanFailure>,
+    pub total_found: usize,
+    /// Whether `max_repos` stopped some found repositories from being analyzed
+    pub truncated: bool,
+}
+
+/// Keep the first `max_repos` discovered repositories, dropping submodules
+/// whose parent was cut so none is linked to a repository that is not analyzed
+fn truncate_discovered(discovered: &mut Vec<DiscoveredRepo>, max_repos: usize) {
+    if discovered.len() <= max_repos {
+        return;
+    }
+    let cut: HashSet<String> = discovered.drain(max_repos..).map(|d| d.path).collect();
+    discovered.retain(|d| d.parent.as_ref().is_none_or(|parent| !cut.contains(parent)));
+}
+
+/// Analyze all repositories found in subdirectories in parallel on the executor
+pub async fn analyze_directory(
+    base_path: &str,
+    options: ScanOptions,
+    executor: &AnalysisExecutor,
+    cancel: &CancellationToken,
+) -> Result<DirectoryAnalysis, GitError> {
+    let base = base_path.to_string();
+    let max_repos = options.max_repos;
+    let include_submodules = options.include_submodules;
+    let walk_cancel = cancel.clone();
+    let mut discovered =
+        tokio::task::spawn_blocking(move || find_repositories(&base, &options, &walk_cancel))
+            .await
+            .map_err(ExecutorError::from)??;
+    let total_found = discovered.len();
+
+    let truncated = max_repos.is_some_and(|max_repos| discovered.len() > max_repos);
+    if let Some(max_repos) = max_repos {
+        truncate_discovered(&mut discovered, max_repos);
+    }
+    let total = discovered.len();
 
     if total == 0 {
-        return Ok(Vec::new());
+        return Ok(DirectoryAnalysis {
+            repos: Vec::new(),
+            failures: Vec::new(),
+            total_found,
+            truncated,
+        });
     }
 
     tracing::info!("Found {} repositories, analyzing in parallel...", total);
 
     let completed = Arc::new(AtomicUsize::new(0));
-    let failed = Arc::new(AtomicUsize::new(0));
-
-    // Use rayon to analyze repositories in parallel
-    let analyses: Vec<RepoAnalysis> = repo_paths
-        .par_iter()
-        .filter_map(|path| {
-            let result = analyze_repository(path);
+    let paths: Vec<String> = discovered.iter().map(|d| d.path.clone()).collect();
+    let job_cancel = cancel.clone();
+    let results = executor
+        .map(paths.clone(), move |path| {
+            let result = analyze_repository(Path::new(&path), include_submodules, &job_cancel);
             let done = completed.fetch_add(1, Ordering::Relaxed) + 1;
-
-            match result {
-                Ok(analysis) => {
-                    if done % 10 == 0 || done == total {
-                        tracing::info!("Progress: {}/{} repositories analyzed", done, total);
-                    }
-                    Some(analysis)
-                }
-                Err(e) => {
-                    failed.fetch_add(1, Ordering::Relaxed);
-                    tracing::warn!("Failed to analyze {}: {}", path, e);
-                    None
-                }
+            if done % 10 == 0 || done == total {
+                tracing::info!("Progress: {}/{} repositories analyzed", done, total);
             }
+            result
         })
+        .await?;
+
+    if cancel.is_cancelled() {
+        tracing::info!("Analysis of {} was cancelled", base_path);
+        return Err(GitError::Cancelled);
+    }
+
+    let mut analyses = Vec::new();
+    let mut failures = Vec::new();
+    for (path, result) in paths.into_iter().zip(results) {
+        match result {
+            Ok(analysis) => analyses.push(analysis),
+            Err(e) => {
+                tracing::warn!("Failed to analyze {}: {}", path, e);
+                failures.push(ScanFailure {
+                    error_kind: e.kind(),
+                    message: e.to_string(),
+                    path,
+                });
+            }
+        }
+    }
+
+    // Link submodules back to the analysis of their parent repository
+    let ids_by_path: HashMap<String, String> = analyses
+        .iter()
+        .map(|a| (a.path.clone(), a.id.clone()))
         .collect();
+    let parents: HashMap<&str, &str> = discovered
+        .iter()
+        .filter_map(|d| Some((d.path.as_str(), d.parent.as_deref()?)))
+        .collect();
+    for analysis in &mut analyses {
+        if let Some(parent_path) = parents.get(analysis.path.as_str()) {
+            analysis.parent_id = ids_by_path.get(*parent_path).cloned();
+        }
+    }
 
-    let failed_count = failed.load(Ordering::Relaxed);
     tracing::info!(
         "Completed: {} succeeded, {} failed",
         analyses.len(),
-        failed_count
+        failures.len()
     );
 
-    Ok(analyses)
+    Ok(DirectoryAnalysis {
+        repos: analyses,
+        failures,
+        total_found,
+        truncated,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{commit_file, git, init_libgit2, init_repo, test_config};
+
+    /// `app` with a file of its own and a submodule `lib` checked out from a sibling repository
+    fn repo_with_submodule(dir: &Path) -> std::path::PathBuf {
+        let lib = dir.join("lib-origin");
+        init_repo(&lib);
+        commit_file(&lib, "src/lib.rs", "fn a() {}\nfn b() {}\nfn c() {}\n", 30);
+
+        let app = dir.join("app");
+        init_repo(&app);
+        commit_file(&app, "main.rs", "fn main() {}\n", 20);
+        git(
+            &app,
+            10,
+            &["submodule", "--quiet", "add", "../lib-origin", "lib"],
+        );
+        git(&app, 10, &["commit", "--quiet", "-m", "add lib"]);
+        app
+    }
+
+    /// A Java layout where `src/main/java` holds a file and `com/acme` splits in two
+    fn java_tree(options: TreeOptions) -> Vec<DirectoryNode> {
+        let dir_stats: HashMap<String, DirStats> = [
+            ("src/main/java", 5),
+            ("src/main/java/com/acme/service", 10),
+            ("src/main/java/com/acme/web", 20),
+        ]
+        .into_iter()
+        .map(|(path, lines)| {
+            let stats = DirStats {
+                lines,
+                languages: HashMap::from([("Java".to_string(), lines)]),
+            };
+            (path.to_string(), stats)
+        })
+        .collect();
+        let age = |path: &str| match path {
+            "src/main" => 50,
+            "src/main/java/com" => 70,
+            _ => 10,
+        };
+        build_directory_tree(&dir_stats, age, options, &CancellationToken::new()).unwrap()
+    }
+
+    #[test]
+    fn keeps_the_full_hierarchy_by_default() {
+        let nodes = java_tree(TreeOptions::default());
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(
+            (nodes[0].name.as_str(), nodes[0].path.as_str()),
+            ("src", "src")
+        );
+        assert_eq!(nodes[0].total_lines, 35);
+    }
+
+    #[test]
+    fn compresses_chains_of_directories_without_files() {
+        let nodes = java_tree(TreeOptions {
+            compress_chains: true,
+        });
+
+        // The chain stops at `java`, which has a file of its own
+        assert_eq!(nodes.len(), 1);
+        let java = &nodes[0];
+        assert_eq!(java.name, "src/main/java");
+        assert_eq!(java.path, "src/main/java");
+        assert_eq!(java.age_days, 50);
+        assert_eq!(java.lines, 5);
+        assert_eq!(java.total_lines, 35);
+
+        // `acme` has two subdirectories, which keep their own paths
+        assert_eq!(java.children.len(), 1);
+        let acme = &java.children[0];
+        assert_eq!(acme.name, "com/acme");
+        assert_eq!(acme.path, "src/main/java/com/acme");
+        assert_eq!(acme.age_days, 70);
+        let paths: Vec<&str> = acme.children.iter().map(|c| c.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            [
+                "src/main/java/com/acme/web",
+                "src/main/java/com/acme/service"
+            ]
+        );
+    }
+
+    #[test]
+    fn directory_ages_come_from_the_commit_adding_each_directory() {
+        init_libgit2();
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path().join("repo");
+        init_repo(&repo);
+        commit_file(&repo, "old/lib.rs", "fn a() {}\n", 400);
+        commit_file(&repo, "new/deep/main.rs", "fn main() {}\n", 10);
+        commit_file(&repo, "old/more.rs", "fn b() {}\n", 5);
+        let paths: HashSet<String> = ["", "old", "new", "new/deep", "untracked"]
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+
+        let ages = get_directory_ages(&repo, &paths, &CancellationToken::new()).unwrap();
+
+        assert_eq!(ages.get(""), Some(&400));
+        assert_eq!(ages.get("old"), Some(&400));
+        assert_eq!(ages.get("new"), Some(&10));
+        assert_eq!(ages.get("new/deep"), Some(&10));
+        assert_eq!(ages.get("untracked"), None);
+    }
+
+    #[test]
+    fn truncation_drops_submodules_of_cut_repositories() {
+        let repo = |path: &str, parent: Option<&str>| DiscoveredRepo {
+            path: path.to_string(),
+            parent: parent.map(str::to_string),
+        };
+        let mut discovered = vec![
+            repo("/src/b/lib", Some("/src/c")),
+            repo("/src/a/lib", Some("/src")),
+            repo("/src/a", None),
+            repo("/src/c", None),
+        ];
+
+        truncate_discovered(&mut discovered, 3);
+
+        let paths: Vec<&str> = discovered.iter().map(|d| d.path.as_str()).collect();
+        assert_eq!(paths, ["/src/a/lib", "/src/a"]);
+    }
+
+    #[test]
+    fn scans_find_repositories_in_name_order() {
+        init_libgit2();
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["c", "a", "b"] {
+            init_repo(&dir.path().join(name));
+        }
+        let base = dir.path().to_string_lossy();
+
+        let found =
+            find_repositories(&base, &ScanOptions::default(), &CancellationToken::new()).unwrap();
+
+        let names: Vec<String> = found
+            .iter()
+            .map(|d| {
+                Path::new(&d.path)
+                    .file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .collect();
+        assert_eq!(names, ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn submodules_count_towards_their_parent_unless_analyzed_separately() {
+        init_libgit2();
+        let dir = tempfile::tempdir().unwrap();
+        let app = repo_with_submodule(dir.path());
+        let cancel = CancellationToken::new();
+
+        let combined = analyze_repository(&app, false, &cancel).unwrap();
+        assert_eq!(combined.total_lines, 4);
+
+        let separate = analyze_repository(&app, true, &cancel).unwrap();
+        assert_eq!(separate.total_lines, 1);
+    }
+
+    #[tokio::test]
+    async fn scans_count_submodules_in_the_parent_when_not_included() {
+        init_libgit2();
+        let dir = tempfile::tempdir().unwrap();
+        let app = repo_with_submodule(dir.path());
+        let cache = tempfile::tempdir().unwrap();
+        let executor = AnalysisExecutor::new(&test_config(cache.path().to_path_buf()));
+        let cancel = CancellationToken::new();
+        let base = dir.path().to_string_lossy().to_string();
+        let find = |repos: &[RepoAnalysis], path: &Path| {
+            repos
+                .iter()
+                .find(|r| Path::new(&r.path) == path)
+                .cloned()
+                .unwrap_or_else(|| panic!("{} not analyzed", path.display()))
+        };
+
+        let scan = analyze_directory(&base, ScanOptions::default(), &executor, &cancel)
+            .await
+            .unwrap();
+        assert_eq!(scan.repos.len(), 2);
+        assert_eq!(find(&scan.repos, &app).total_lines, 4);
+
+        let options = ScanOptions {
+            include_submodules: true,
+            ..ScanOptions::default()
+        };
+        let scan = analyze_directory(&base, options, &executor, &cancel)
+            .await
+            .unwrap();
+        assert_eq!(scan.repos.len(), 3);
+        let parent = find(&scan.repos, &app);
+        let submodule = find(&scan.repos, &app.join("lib"));
+        assert_eq!(parent.total_lines, 1);
+        assert_eq!(submodule.total_lines, 3);
+        assert_eq!(submodule.parent_id, Some(parent.id));
+    }
 }
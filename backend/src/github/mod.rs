@@ -1,66 +1,679 @@
-use crate::git::{analyze_repository, GitError};
-use crate::models::RepoAnalysis;
-use octocrab::Octocrab;
-use std::process::Command;
-use tempfile::TempDir;
-use thiserror::Error;
-
-#[derive(Error, Debug)]
-pub enum GithubError {
-    #[error("GitHub API error: {0}")]
-    Api(#[from] octocrab::Error),
-    #[error("Git clone failed: {0}")]
-    Clone(String),
-    #[error("Git analysis error: {0}")]
-    Analysis(#[from] GitError),
-    #[error("IO error: {0}")]
-    Io(#[from] std::io::Error),
+use crate::config::Config;
+use crate::git::{
+    build_directory_tree, calculate_language_breakdown, is_ignored, DirStats, TreeOptions,
+};
+use crate::models::{
+    get_language_from_extension, ProviderKind, RemoteMetadata, RepoAnalysis, RepoStatus,
+};
+use crate::provider::{ProviderError, RemoteProvider, RemoteRepo};
+use crate::remote::RemoteCredentials;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use octocrab::models::Repository;
+use octocrab::{Octocrab, Page};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+/// Endpoint and credentials used to reach GitHub or a GitHub Enterprise server
+#[derive(Clone, Default)]
+pub struct GithubClientConfig {
+    /// API base URL; defaults to `https://api.github.com`
+    pub api_url: Option<String>,
+    pub token: Option<String>,
+}
+
+impl GithubClientConfig {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            api_url: config.github_api_url.clone(),
+            token: config.github_token.clone(),
+        }
+    }
+
+    /// Use a per-request token in place of the configured one
+    pub fn with_token(&self, token: Option<String>) -> Self {
+        Self {
+            api_url: self.api_url.clone(),
+            token: token.or_else(|| self.token.clone()),
+        }
+    }
+
+    pub fn client(&self) -> Result<Octocrab, ProviderError> {
+        let mut builder = Octocrab::builder();
+        if let Some(api_url) = &self.api_url {
+            builder = builder
+                .base_uri(api_url.as_str())
+                .map_err(ProviderError::github)?;
+        }
+        if let Some(token) = &self.token {
+            builder = builder.personal_token(token.clone());
+        }
+        builder.build().map_err(ProviderError::github)
+    }
+}
+
+#[async_trait]
+impl RemoteProvider for GithubClientConfig {
+    async fn resolve_repo(&self, owner: &str, repo: &str) -> Result<RemoteRepo, ProviderError> {
+        let octocrab = self.client()?;
+        let repo_info = octocrab
+            .repos(owner, repo)
+            .get()
+            .await
+            .map_err(ProviderError::github)?;
+        Ok(remote_repo(&repo_info))
+    }
+
+    async fn list_owner_repos(&self, owner: &str) -> Result<Vec<RemoteRepo>, ProviderError> {
+        let octocrab = self.client()?;
+        let list = async {
+            let orgs = octocrab.orgs(owner);
+            let first_page = match orgs.list_repos().per_page(100).send().await {
+                Ok(page) => page,
+                Err(octocrab::Error::GitHub { source, .. })
+                    if source.status_code.as_u16() == 404 =>
+                {
+                    octocrab.users(owner).repos().per_page(100).send().await?
+                }
+                Err(e) => return Err(e),
+            };
+            octocrab.all_pages(first_page).await
+        };
+        let repos = list.await.map_err(ProviderError::github)?;
+        Ok(repos.iter().map(remote_repo).collect())
+    }
+
+    fn clone_credentials(&self) -> RemoteCredentials {
+        RemoteCredentials::token(self.token.clone())
+    }
+
+    fn tree_url(&self, repo: &RemoteRepo) -> Option<String> {
+        let html_url = repo.metadata.html_url.as_ref()?;
+        let branch = repo.metadata.default_branch.as_ref()?;
+        Some(format!("{}/tree/{}/", html_url, branch))
+    }
+}
+
+fn remote_repo(repo_info: &Repository) -> RemoteRepo {
+    RemoteRepo {
+        full_name: repo_info
+            .full_name
+            .clone()
+            .unwrap_or_else(|| repo_info.name.clone()),
+        clone_url: repo_info.clone_url.as_ref().map(|url| url.to_string()),
+        language: repo_info
+            .language
+            .as_ref()
+            .and_then(|l| l.as_str())
+            .map(str::to_string),
+        metadata: RemoteMetadata {
+            provider: ProviderKind::Github,
+            description: repo_info.description.clone(),
+            default_branch: repo_info.default_branch.clone(),
+            topics: repo_info.topics.clone().unwrap_or_default(),
+            stars: repo_info.stargazers_count.unwrap_or(0),
+            archived: repo_info.archived.unwrap_or(false),
+            fork: repo_info.fork.unwrap_or(false),
+            created_at: repo_info.created_at,
+            pushed_at: repo_info.pushed_at,
+            html_url: repo_info.html_url.as_ref().map(|url| url.to_string()),
+            tree_url: None,
+        },
+    }
+}
+
+/// Typical bytes per line, used to estimate line counts from blob sizes
+fn average_line_bytes(language: &str) -> u64 {
+    match language {
+        "Java" | "C#" | "Kotlin" | "Scala" | "Swift" | "Dart" => 38,
+        "HTML" | "XML" | "Vue" | "Svelte" | "Markdown" => 45,
+        "JSON" | "YAML" | "TOML" | "CSS" | "SCSS" | "Sass" => 22,
+        "Python" | "Ruby" | "Elixir" | "Haskell" | "Clojure" | "Shell" => 30,
+        _ => 32,
+    }
 }
 
-pub async fn analyze_github_repo(owner: &str, repo: &str) -> Result<RepoAnalysis, GithubError> {
-    // Get repo info from GitHub API
-    let octocrab = Octocrab::builder().build()?;
-    let repo_info = octocrab.repos(owner, repo).get().await?;
-
-    let clone_url = repo_info
-        .clone_url
-        .ok_or_else(|| GithubError::Clone("No clone URL available".to_string()))?;
-
-    // Clone to temp directory
-    let temp_dir = TempDir::new()?;
-    let temp_path = temp_dir.path();
-
-    // Shallow clone for faster analysis
-    let output = Command::new("git")
-        .args([
-            "clone",
-            "--depth",
-            "1",
-            "--single-branch",
-            clone_url.as_str(),
-            temp_path.to_str().unwrap(),
-        ])
-        .output()?;
-
-    if !output.status.success() {
-        return Err(GithubError::Clone(
-            String::from_utf8_lossy(&output.stderr).to_string(),
-        ));
-    }
-
-    // For age, we need to fetch the first commit
-    // Do a separate fetch for the oldest commit
-    let _ = Command::new("git")
-        .args(["fetch", "--deepen=2147483647"])
-        .current_dir(temp_path)
-        .output();
-
-    // Analyze the cloned repo
-    let mut analysis = analyze_repository(temp_path.to_str().unwrap())?;
-
-    // Override name with GitHub repo name
-    analysis.name = format!("{}/{}", owner, repo);
-    analysis.path = clone_url.to_string();
-
-    Ok(analysis)
+#[derive(Debug, Deserialize)]
+struct GitTree {
+    tree: Vec<GitTreeEntry>,
+    #[serde(default)]
+    truncated: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct GitTreeEntry {
+    path: String,
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    size: Option<u64>,
+}
+
+/// Only the commit dates of an entry from the commits API
+#[derive(Debug, Deserialize)]
+struct CommitDates {
+    commit: CommitDatesDetail,
+}
+
+#[derive(Debug, Deserialize)]
+struct CommitDatesDetail {
+    author: Option<CommitDate>,
+    committer: Option<CommitDate>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CommitDate {
+    date: Option<DateTime<Utc>>,
+}
+
+/// Estimate a repository's analysis from the GitHub API without cloning it.
+///
+/// Lines are estimated from blob sizes in the recursive Git Trees API and
+/// the repository's age comes from its oldest commit, found on the last
+/// page of the commits API. Directory ages are the repository's age. Trees
+/// too large for one response are truncated by GitHub; the analysis is then
+/// flagged as `truncated`.
+pub async fn estimate_github_repo(
+    owner: &str,
+    repo: &str,
+    github: &GithubClientConfig,
+    cancel: &CancellationToken,
+) -> Result<RepoAnalysis, ProviderError> {
+    let octocrab = github.client()?;
+    let fetch = async {
+        let repo_info = octocrab.repos(owner, repo).get().await?;
+        let branch = repo_info
+            .default_branch
+            .clone()
+            .unwrap_or_else(|| "HEAD".to_string());
+
+        let tree_route = format!("/repos/{}/{}/git/trees/{}", owner, repo, branch);
+        let tree = match octocrab
+            .get::<GitTree, _, _>(tree_route, Some(&[("recursive", "1")]))
+            .await
+        {
+            Ok(tree) => Some(tree),
+            // GitHub answers 409 Conflict for a repository without commits
+            Err(octocrab::Error::GitHub { source, .. }) if source.status_code.as_u16() == 409 => {
+                None
+            }
+            Err(e) => return Err(e),
+        };
+        let age_days = match tree {
+            Some(_) => oldest_commit_age_days(&octocrab, owner, repo, &branch).await?,
+            None => 0,
+        };
+        Ok((repo_info, tree, age_days))
+    };
+    let (repo_info, tree, age_days) = tokio::select! {
+        result = fetch => result.map_err(ProviderError::github)?,
+        _ = cancel.cancelled() => return Err(ProviderError::Cancelled),
+    };
+    let remote_repo = remote_repo(&repo_info);
+
+    let mut total_lines = 0u64;
+    let mut language_stats: HashMap<String, u64> = HashMap::new();
+    let mut dir_stats: HashMap<String, DirStats> = HashMap::new();
+    if let Some(tree) = &tree {
+        if tree.truncated {
+            tracing::warn!(
+                "Git tree of {}/{} is too large for one response and was truncated",
+                owner,
+                repo
+            );
+        }
+        for entry in tree.tree.iter().filter(|entry| entry.kind == "blob") {
+            let path = Path::new(&entry.path);
+            if is_ignored(path, Path::new("")) {
+                continue;
+            }
+            let Some(language) = path
+                .extension()
+                .and_then(|ext| get_language_from_extension(&ext.to_string_lossy()))
+            else {
+                continue;
+            };
+            let lines = entry
+                .size
+                .unwrap_or(0)
+                .div_ceil(average_line_bytes(language));
+
+            total_lines += lines;
+            *language_stats.entry(language.to_string()).or_insert(0) += lines;
+            let dir = path
+                .parent()
+                .map(|p| p.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let dir_stat = dir_stats.entry(dir).or_default();
+            dir_stat.lines += lines;
+            *dir_stat.languages.entry(language.to_string()).or_insert(0) += lines;
+        }
+    }
+
+    let directories =
+        build_directory_tree(&dir_stats, |_| age_days, TreeOptions::default(), cancel)?;
+    let languages = calculate_language_breakdown(&language_stats, total_lines);
+
+    Ok(RepoAnalysis {
+        id: Uuid::new_v4().to_string(),
+        name: format!("{}/{}", owner, repo),
+        path: remote_repo
+            .clone_url
+            .clone()
+            .unwrap_or_else(|| format!("{}/{}", owner, repo)),
+        age_days,
+        total_lines,
+        languages,
+        directories,
+        status: if tree.is_some() {
+            RepoStatus::Ok
+        } else {
+            RepoStatus::Empty
+        },
+        parent_id: None,
+        remote: Some(RemoteMetadata {
+            tree_url: github.tree_url(&remote_repo),
+            ..remote_repo.metadata
+        }),
+        estimated: true,
+        display_name: None,
+        clone_strategy: None,
+        approximate_directory_ages: true,
+        truncated: tree.as_ref().is_some_and(|tree| tree.truncated),
+    })
+}
+
+/// Age in days of the oldest commit on a branch.
+///
+/// With one commit per page, the last page of the commits API, linked from
+/// the first, holds the root commit.
+async fn oldest_commit_age_days(
+    octocrab: &Octocrab,
+    owner: &str,
+    repo: &str,
+    branch: &str,
+) -> Result<u64, octocrab::Error> {
+    let route = format!("/repos/{}/{}/commits", owner, repo);
+    let first: Page<CommitDates> = octocrab
+        .get(route, Some(&[("sha", branch), ("per_page", "1")]))
+        .await?;
+    let last = match octocrab.get_page::<CommitDates>(&first.last).await? {
+        Some(page) => page,
+        None => first,
+    };
+
+    let oldest = last.items.last().and_then(|entry| {
+        let detail = &entry.commit;
+        detail
+            .committer
+            .as_ref()
+            .and_then(|c| c.date)
+            .or_else(|| detail.author.as_ref().and_then(|a| a.date))
+    });
+    Ok(oldest
+        .map(|date| (Utc::now() - date).num_days().max(0) as u64)
+        .unwrap_or(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::serve;
+    use axum::extract::{Path as UrlPath, Query, State};
+    use axum::http::{header, HeaderMap, StatusCode};
+    use axum::response::{IntoResponse, Response};
+    use axum::routing::get;
+    use axum::{Json, Router};
+    use chrono::Duration;
+    use serde_json::{json, Value};
+    use std::sync::{Arc, Mutex};
+
+    /// Authorization headers received by the stand-in server
+    type SeenAuth = Arc<Mutex<Vec<Option<String>>>>;
+
+    async fn get_repo(
+        State(seen): State<SeenAuth>,
+        UrlPath((owner, repo)): UrlPath<(String, String)>,
+        headers: HeaderMap,
+    ) -> Json<Value> {
+        let auth = headers
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        seen.lock().unwrap().push(auth);
+        Json(json!({
+            "id": 1,
+            "name": repo,
+            "full_name": format!("{}/{}", owner, repo),
+            "url": format!("https://api.github.example/repos/{}/{}", owner, repo),
+            "html_url": format!("https://github.example/{}/{}", owner, repo),
+            "clone_url": format!("https://github.example/{}/{}.git", owner, repo),
+            "default_branch": "main",
+        }))
+    }
+
+    #[tokio::test]
+    async fn request_token_reaches_api_calls_and_clone_credentials() {
+        let seen = SeenAuth::default();
+        let api_url = serve(
+            Router::new()
+                .route("/repos/{owner}/{repo}", get(get_repo))
+                .with_state(seen.clone()),
+        )
+        .await;
+        let configured = GithubClientConfig {
+            api_url: Some(api_url),
+            token: Some("configured-token".to_string()),
+        };
+
+        let github = configured.with_token(Some("request-token".to_string()));
+        let repo = github.resolve_repo("octo", "city").await.unwrap();
+
+        assert_eq!(repo.full_name, "octo/city");
+        assert_eq!(
+            repo.clone_url.as_deref(),
+            Some("https://github.example/octo/city.git")
+        );
+        assert_eq!(
+            github.tree_url(&repo).as_deref(),
+            Some("https://github.example/octo/city/tree/main/")
+        );
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![Some("Bearer request-token".to_string())]
+        );
+        assert_eq!(
+            github.clone_credentials().token.as_deref(),
+            Some("request-token")
+        );
+    }
+
+    #[tokio::test]
+    async fn configured_token_is_used_without_a_request_token() {
+        let seen = SeenAuth::default();
+        let api_url = serve(
+            Router::new()
+                .route("/repos/{owner}/{repo}", get(get_repo))
+                .with_state(seen.clone()),
+        )
+        .await;
+        let github = GithubClientConfig {
+            api_url: Some(api_url),
+            token: Some("configured-token".to_string()),
+        }
+        .with_token(None);
+
+        github.resolve_repo("octo", "city").await.unwrap();
+
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![Some("Bearer configured-token".to_string())]
+        );
+        assert_eq!(
+            github.clone_credentials().token.as_deref(),
+            Some("configured-token")
+        );
+    }
+
+    /// Days ago of the commits on the stand-in's default branch, newest first
+    const COMMIT_DAYS_AGO: [i64; 3] = [1, 40, 300];
+
+    async fn get_tree(
+        truncated: bool,
+        UrlPath((_, _, branch)): UrlPath<(String, String, String)>,
+        Query(query): Query<HashMap<String, String>>,
+    ) -> Response {
+        if branch != "main" || query.get("recursive").map(String::as_str) != Some("1") {
+            return StatusCode::BAD_REQUEST.into_response();
+        }
+        Json(json!({
+            "sha": "abc",
+            "truncated": truncated,
+            "tree": [
+                { "path": "src", "type": "tree" },
+                { "path": "src/main.rs", "type": "blob", "size": 320 },
+                { "path": "src/util", "type": "tree" },
+                { "path": "src/util/helpers.py", "type": "blob", "size": 300 },
+                { "path": "docs/notes.txt", "type": "blob", "size": 5000 },
+                { "path": "node_modules/dep/index.js", "type": "blob", "size": 9000 },
+            ],
+        }))
+        .into_response()
+    }
+
+    /// One commit per page, linking to the next and last pages like GitHub
+    async fn list_commits(
+        UrlPath((owner, repo)): UrlPath<(String, String)>,
+        Query(query): Query<HashMap<String, String>>,
+        headers: HeaderMap,
+    ) -> Response {
+        if query.get("sha").map(String::as_str) != Some("main")
+            || query.get("per_page").map(String::as_str) != Some("1")
+        {
+            return StatusCode::BAD_REQUEST.into_response();
+        }
+        let page: usize = query.get("page").and_then(|p| p.parse().ok()).unwrap_or(1);
+        let Some(days_ago) = COMMIT_DAYS_AGO.get(page - 1) else {
+            return Json(json!([])).into_response();
+        };
+        let date = (Utc::now() - Duration::days(*days_ago)).to_rfc3339();
+        let body = Json(json!([{
+            "sha": format!("commit-{}", page),
+            "commit": {
+                "author": { "date": date },
+                "committer": { "date": date },
+            },
+        }]));
+
+        let host = headers[header::HOST].to_str().unwrap();
+        let page_url = |page: usize| {
+            format!(
+                "<http://{}/repos/{}/{}/commits?sha=main&per_page=1&page={}>",
+                host, owner, repo, page
+            )
+        };
+        let last = COMMIT_DAYS_AGO.len();
+        if page < last {
+            let link = format!(
+                "{}; rel=\"next\", {}; rel=\"last\"",
+                page_url(page + 1),
+                page_url(last)
+            );
+            ([(header::LINK, link)], body).into_response()
+        } else {
+            body.into_response()
+        }
+    }
+
+    async fn serve_repo(truncated: bool) -> GithubClientConfig {
+        let api_url = serve(
+            Router::new()
+                .route("/repos/{owner}/{repo}", get(get_repo))
+                .route(
+                    "/repos/{owner}/{repo}/git/trees/{branch}",
+                    get(move |path, query| get_tree(truncated, path, query)),
+                )
+                .route("/repos/{owner}/{repo}/commits", get(list_commits))
+                .with_state(SeenAuth::default()),
+        )
+        .await;
+        GithubClientConfig {
+            api_url: Some(api_url),
+            token: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn estimates_lines_and_age_from_trees_and_commits() {
+        let github = serve_repo(false).await;
+
+        let analysis = estimate_github_repo("octo", "city", &github, &CancellationToken::new())
+            .await
+            .unwrap();
+
+        assert_eq!(analysis.name, "octo/city");
+        assert_eq!(analysis.path, "https://github.example/octo/city.git");
+        assert!(analysis.estimated);
+        assert!(!analysis.truncated);
+        assert_eq!(analysis.status, RepoStatus::Ok);
+        // 320 bytes of Rust at 32 bytes a line and 300 of Python at 30
+        assert_eq!(analysis.total_lines, 20);
+        let languages: Vec<(&str, u64)> = analysis
+            .languages
+            .iter()
+            .map(|l| (l.language.as_str(), l.lines))
+            .collect();
+        assert!(languages.contains(&("Rust", 10)));
+        assert!(languages.contains(&("Python", 10)));
+        // The oldest commit is on the last page
+        assert_eq!(analysis.age_days, 300);
+
+        assert_eq!(analysis.directories.len(), 1);
+        let src = &analysis.directories[0];
+        assert_eq!(src.path, "src");
+        assert_eq!(src.lines, 10);
+        assert_eq!(src.total_lines, 20);
+        assert_eq!(src.age_days, 300);
+        assert_eq!(src.children[0].path, "src/util");
+        assert_eq!(
+            analysis.remote.unwrap().tree_url.as_deref(),
+            Some("https://github.example/octo/city/tree/main/")
+        );
+    }
+
+    #[tokio::test]
+    async fn flags_analyses_of_truncated_trees() {
+        let github = serve_repo(true).await;
+
+        let analysis = estimate_github_repo("octo", "city", &github, &CancellationToken::new())
+            .await
+            .unwrap();
+
+        assert!(analysis.truncated);
+        assert_eq!(analysis.total_lines, 20);
+    }
+
+    /// Repositories of `owner` in pages of two, linked like GitHub's listings
+    fn repo_page(
+        owner: &str,
+        count: usize,
+        route: &str,
+        query: &HashMap<String, String>,
+        headers: &HeaderMap,
+    ) -> Response {
+        let page: usize = query.get("page").and_then(|p| p.parse().ok()).unwrap_or(1);
+        let repos: Vec<Value> = (1..=count)
+            .skip((page - 1) * 2)
+            .take(2)
+            .map(|n| {
+                json!({
+                    "id": n,
+                    "name": format!("repo-{}", n),
+                    "full_name": format!("{}/repo-{}", owner, n),
+                    "url": format!("https://api.github.example/repos/{}/repo-{}", owner, n),
+                    "clone_url": format!("https://github.example/{}/repo-{}.git", owner, n),
+                })
+            })
+            .collect();
+        if page * 2 < count {
+            let host = headers[header::HOST].to_str().unwrap();
+            let link = format!(
+                "<http://{}{}?per_page=100&page={}>; rel=\"next\"",
+                host,
+                route,
+                page + 1
+            );
+            ([(header::LINK, link)], Json(repos)).into_response()
+        } else {
+            Json(repos).into_response()
+        }
+    }
+
+    fn not_found() -> Response {
+        (
+            StatusCode::NOT_FOUND,
+            Json(json!({
+                "message": "Not Found",
+                "documentation_url": "https://docs.github.com/rest",
+            })),
+        )
+            .into_response()
+    }
+
+    async fn org_repos(
+        UrlPath(org): UrlPath<String>,
+        Query(query): Query<HashMap<String, String>>,
+        headers: HeaderMap,
+    ) -> Response {
+        match org.as_str() {
+            "acme" => repo_page("acme", 5, "/orgs/acme/repos", &query, &headers),
+            _ => not_found(),
+        }
+    }
+
+    async fn user_repos(
+        UrlPath(user): UrlPath<String>,
+        Query(query): Query<HashMap<String, String>>,
+        headers: HeaderMap,
+    ) -> Response {
+        match user.as_str() {
+            "someone" => repo_page("someone", 3, "/users/someone/repos", &query, &headers),
+            _ => not_found(),
+        }
+    }
+
+    async fn serve_owners() -> GithubClientConfig {
+        let api_url = serve(
+            Router::new()
+                .route("/orgs/{org}/repos", get(org_repos))
+                .route("/users/{user}/repos", get(user_repos)),
+        )
+        .await;
+        GithubClientConfig {
+            api_url: Some(api_url),
+            token: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn pages_through_organization_repos() {
+        let github = serve_owners().await;
+
+        let repos = github.list_owner_repos("acme").await.unwrap();
+
+        let names: Vec<&str> = repos.iter().map(|r| r.full_name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "acme/repo-1",
+                "acme/repo-2",
+                "acme/repo-3",
+                "acme/repo-4",
+                "acme/repo-5",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_user_repos_when_no_organization_exists() {
+        let github = serve_owners().await;
+
+        let repos = github.list_owner_repos("someone").await.unwrap();
+
+        let names: Vec<&str> = repos.iter().map(|r| r.full_name.as_str()).collect();
+        assert_eq!(
+            names,
+            ["someone/repo-1", "someone/repo-2", "someone/repo-3"]
+        );
+
+        match github.list_owner_repos("nobody").await {
+            Err(ProviderError::Api {
+                status: Some(404), ..
+            }) => {}
+            other => panic!("expected a 404 API error, got {:?}", other),
+        }
+    }
 }
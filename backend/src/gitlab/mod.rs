@@ -0,0 +1,434 @@
+use crate::config::Config;
+use crate::models::{ProviderKind, RemoteMetadata};
+use crate::provider::{ProviderError, RemoteProvider, RemoteRepo};
+use crate::remote::RemoteCredentials;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use reqwest::Response;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// Projects requested per page when listing a group or user
+const PER_PAGE: &str = "100";
+
+/// Endpoint and credentials used to reach gitlab.com or a self-managed GitLab
+#[derive(Clone)]
+pub struct GitlabClientConfig {
+    /// Instance URL, such as `https://gitlab.com`
+    pub url: String,
+    pub token: Option<String>,
+}
+
+impl GitlabClientConfig {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            url: config.gitlab_url.clone(),
+            token: config.gitlab_token.clone(),
+        }
+    }
+
+    /// Use a per-request token in place of the configured one
+    pub fn with_token(&self, token: Option<String>) -> Self {
+        Self {
+            url: self.url.clone(),
+            token: token.or_else(|| self.token.clone()),
+        }
+    }
+
+    /// Client for the REST API at `<url>/api/v4`
+    fn client(&self) -> Result<GitlabClient, ProviderError> {
+        Ok(GitlabClient {
+            http: reqwest::Client::builder().build().map_err(api_error)?,
+            api_url: format!("{}/api/v4", self.url.trim_end_matches('/')),
+            token: self.token.clone(),
+        })
+    }
+}
+
+/// Error body of the GitLab API; `message` may also be an object of field errors
+#[derive(Debug, Deserialize)]
+struct GitlabErrorBody {
+    message: Option<serde_json::Value>,
+    error: Option<String>,
+}
+
+/// Minimal GitLab REST client following `X-Next-Page` pagination
+struct GitlabClient {
+    http: reqwest::Client,
+    api_url: String,
+    token: Option<String>,
+}
+
+impl GitlabClient {
+    async fn send(&self, route: &str, query: &[(&str, &str)]) -> Result<Response, ProviderError> {
+        let mut request = self
+            .http
+            .get(format!("{}{}", self.api_url, route))
+            .query(query);
+        if let Some(token) = &self.token {
+            request = request.header("PRIVATE-TOKEN", token);
+        }
+        let response = request.send().await.map_err(api_error)?;
+        if response.status().is_success() {
+            Ok(response)
+        } else {
+            Err(status_error(response).await)
+        }
+    }
+
+    async fn get<T: DeserializeOwned>(
+        &self,
+        route: &str,
+        query: &[(&str, &str)],
+    ) -> Result<T, ProviderError> {
+        self.send(route, query)
+            .await?
+            .json()
+            .await
+            .map_err(api_error)
+    }
+
+    /// Every item of a paginated listing
+    async fn get_all<T: DeserializeOwned>(
+        &self,
+        route: &str,
+        query: &[(&str, &str)],
+    ) -> Result<Vec<T>, ProviderError> {
+        let mut items = Vec::new();
+        let mut page = "1".to_string();
+        loop {
+            let mut page_query = query.to_vec();
+            page_query.push(("page", &page));
+            let response = self.send(route, &page_query).await?;
+            let next_page = response
+                .headers()
+                .get("x-next-page")
+                .and_then(|value| value.to_str().ok())
+                .filter(|value| !value.is_empty())
+                .map(str::to_string);
+            items.extend(response.json::<Vec<T>>().await.map_err(api_error)?);
+            match next_page {
+                Some(next) => page = next,
+                None => return Ok(items),
+            }
+        }
+    }
+}
+
+fn api_error(e: reqwest::Error) -> ProviderError {
+    ProviderError::Api {
+        provider: ProviderKind::Gitlab,
+        status: e.status().map(|status| status.as_u16()),
+        message: e.to_string(),
+    }
+}
+
+/// Describe an unsuccessful response from its status and error body
+async fn status_error(response: Response) -> ProviderError {
+    let status = response.status();
+    let body = response.text().await.unwrap_or_default();
+    let message = match serde_json::from_str::<GitlabErrorBody>(&body) {
+        Ok(GitlabErrorBody {
+            message: Some(serde_json::Value::String(message)),
+            ..
+        }) => message,
+        Ok(GitlabErrorBody {
+            message: Some(message),
+            ..
+        }) => message.to_string(),
+        Ok(GitlabErrorBody {
+            error: Some(error), ..
+        }) => error,
+        _ => status
+            .canonical_reason()
+            .unwrap_or("Unexpected response")
+            .to_string(),
+    };
+    ProviderError::Api {
+        provider: ProviderKind::Gitlab,
+        status: Some(status.as_u16()),
+        message,
+    }
+}
+
+/// A project as returned by the GitLab projects API
+#[derive(Debug, Deserialize)]
+struct GitlabProject {
+    path_with_namespace: String,
+    http_url_to_repo: Option<String>,
+    web_url: Option<String>,
+    description: Option<String>,
+    default_branch: Option<String>,
+    #[serde(default)]
+    topics: Vec<String>,
+    #[serde(default)]
+    star_count: u32,
+    #[serde(default)]
+    archived: bool,
+    /// Present only on forks
+    forked_from_project: Option<serde_json::Value>,
+    created_at: Option<DateTime<Utc>>,
+    last_activity_at: Option<DateTime<Utc>>,
+}
+
+impl From<GitlabProject> for RemoteRepo {
+    fn from(project: GitlabProject) -> Self {
+        RemoteRepo {
+            full_name: project.path_with_namespace,
+            clone_url: project.http_url_to_repo,
+            // Project listings leave out languages; see `primary_language`
+            language: None,
+            metadata: RemoteMetadata {
+                provider: ProviderKind::Gitlab,
+                description: project.description,
+                default_branch: project.default_branch,
+                topics: project.topics,
+                stars: project.star_count,
+                archived: project.archived,
+                fork: project.forked_from_project.is_some(),
+                created_at: project.created_at,
+                pushed_at: project.last_activity_at,
+                html_url: project.web_url,
+                tree_url: None,
+            },
+        }
+    }
+}
+
+/// Encode a namespaced path for use as a single URL path segment
+fn encode_path(path: &str) -> String {
+    url::form_urlencoded::byte_serialize(path.as_bytes()).collect()
+}
+
+#[async_trait]
+impl RemoteProvider for GitlabClientConfig {
+    async fn resolve_repo(&self, owner: &str, repo: &str) -> Result<RemoteRepo, ProviderError> {
+        let route = format!("/projects/{}", encode_path(&format!("{}/{}", owner, repo)));
+        let project: GitlabProject = self.client()?.get(&route, &[]).await?;
+        Ok(project.into())
+    }
+
+    async fn list_owner_repos(&self, owner: &str) -> Result<Vec<RemoteRepo>, ProviderError> {
+        let client = self.client()?;
+        let group_route = format!("/groups/{}/projects", encode_path(owner));
+        let group_query = [("include_subgroups", "true"), ("per_page", PER_PAGE)];
+        let projects: Vec<GitlabProject> = match client.get_all(&group_route, &group_query).await {
+            Err(ProviderError::Api {
+                status: Some(404), ..
+            }) => {
+                let user_route = format!("/users/{}/projects", encode_path(owner));
+                client
+                    .get_all(&user_route, &[("per_page", PER_PAGE)])
+                    .await?
+            }
+            result => result?,
+        };
+        Ok(projects.into_iter().map(RemoteRepo::from).collect())
+    }
+
+    async fn primary_language(&self, repo: &RemoteRepo) -> Result<Option<String>, ProviderError> {
+        let route = format!("/projects/{}/languages", encode_path(&repo.full_name));
+        let languages: HashMap<String, f64> = self.client()?.get(&route, &[]).await?;
+        Ok(languages
+            .into_iter()
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(language, _)| language))
+    }
+
+    fn clone_credentials(&self) -> RemoteCredentials {
+        RemoteCredentials {
+            username: Some("oauth2".to_string()),
+            token: self.token.clone(),
+        }
+    }
+
+    fn tree_url(&self, repo: &RemoteRepo) -> Option<String> {
+        let web_url = repo.metadata.html_url.as_ref()?;
+        let branch = repo.metadata.default_branch.as_ref()?;
+        Some(format!("{}/-/tree/{}/", web_url, branch))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::serve;
+    use axum::extract::{Path, Query};
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::response::{IntoResponse, Response};
+    use axum::routing::get;
+    use axum::{Json, Router};
+    use serde_json::{json, Value};
+
+    fn project(path_with_namespace: &str) -> Value {
+        json!({
+            "path_with_namespace": path_with_namespace,
+            "http_url_to_repo": format!("https://gitlab.example/{}.git", path_with_namespace),
+            "web_url": format!("https://gitlab.example/{}", path_with_namespace),
+            "default_branch": "main",
+            "topics": ["city"],
+            "star_count": 3,
+            "created_at": "2020-01-01T00:00:00Z",
+        })
+    }
+
+    /// Projects of `owner` in pages of two, announcing the next page like GitLab
+    fn project_page(owner: &str, count: usize, query: &HashMap<String, String>) -> Response {
+        let page: usize = query.get("page").and_then(|p| p.parse().ok()).unwrap_or(1);
+        let projects: Vec<Value> = (1..=count)
+            .skip((page - 1) * 2)
+            .take(2)
+            .map(|n| project(&format!("{}/project-{}", owner, n)))
+            .collect();
+        let next_page = if page * 2 < count {
+            (page + 1).to_string()
+        } else {
+            String::new()
+        };
+        ([("x-next-page", next_page)], Json(projects)).into_response()
+    }
+
+    fn not_found() -> Response {
+        (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "message": "404 Group Not Found" })),
+        )
+            .into_response()
+    }
+
+    async fn get_project(Path(id): Path<String>, headers: HeaderMap) -> Response {
+        if headers.get("private-token").is_none_or(|t| t != "secret") {
+            return (
+                StatusCode::UNAUTHORIZED,
+                Json(json!({ "message": "401 Unauthorized" })),
+            )
+                .into_response();
+        }
+        match id.as_str() {
+            "group/sub/project" => Json(project(&id)).into_response(),
+            _ => (
+                StatusCode::NOT_FOUND,
+                Json(json!({ "message": "404 Project Not Found" })),
+            )
+                .into_response(),
+        }
+    }
+
+    async fn group_projects(
+        Path(group): Path<String>,
+        Query(query): Query<HashMap<String, String>>,
+    ) -> Response {
+        match group.as_str() {
+            "group" if query.get("include_subgroups").map(String::as_str) == Some("true") => {
+                project_page("group", 5, &query)
+            }
+            _ => not_found(),
+        }
+    }
+
+    async fn user_projects(
+        Path(user): Path<String>,
+        Query(query): Query<HashMap<String, String>>,
+    ) -> Response {
+        match user.as_str() {
+            "someone" => project_page("someone", 3, &query),
+            _ => not_found(),
+        }
+    }
+
+    /// Stand-in GitLab instance accepting the token `secret`
+    async fn serve_gitlab(token: Option<&str>) -> GitlabClientConfig {
+        let url = serve(
+            Router::new()
+                .route("/api/v4/projects/{id}", get(get_project))
+                .route("/api/v4/groups/{group}/projects", get(group_projects))
+                .route("/api/v4/users/{user}/projects", get(user_projects)),
+        )
+        .await;
+        GitlabClientConfig {
+            url: format!("{}/", url),
+            token: token.map(str::to_string),
+        }
+    }
+
+    #[tokio::test]
+    async fn resolves_a_project_in_a_subgroup() {
+        let gitlab = serve_gitlab(Some("secret")).await;
+
+        let repo = gitlab.resolve_repo("group/sub", "project").await.unwrap();
+
+        assert_eq!(repo.full_name, "group/sub/project");
+        assert_eq!(
+            repo.clone_url.as_deref(),
+            Some("https://gitlab.example/group/sub/project.git")
+        );
+        assert_eq!(repo.metadata.provider, ProviderKind::Gitlab);
+        assert_eq!(repo.metadata.topics, vec!["city".to_string()]);
+        assert_eq!(repo.metadata.stars, 3);
+        assert_eq!(
+            gitlab.tree_url(&repo).as_deref(),
+            Some("https://gitlab.example/group/sub/project/-/tree/main/")
+        );
+        let credentials = gitlab.clone_credentials();
+        assert_eq!(credentials.username.as_deref(), Some("oauth2"));
+        assert_eq!(credentials.token.as_deref(), Some("secret"));
+    }
+
+    #[tokio::test]
+    async fn pages_through_group_projects() {
+        let gitlab = serve_gitlab(None).await;
+
+        let repos = gitlab.list_owner_repos("group").await.unwrap();
+
+        let names: Vec<&str> = repos.iter().map(|r| r.full_name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "group/project-1",
+                "group/project-2",
+                "group/project-3",
+                "group/project-4",
+                "group/project-5",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_user_projects_when_no_group_exists() {
+        let gitlab = serve_gitlab(None).await;
+
+        let repos = gitlab.list_owner_repos("someone").await.unwrap();
+
+        let names: Vec<&str> = repos.iter().map(|r| r.full_name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "someone/project-1",
+                "someone/project-2",
+                "someone/project-3"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn reports_status_and_message_of_error_responses() {
+        let gitlab = serve_gitlab(Some("secret")).await;
+
+        match gitlab.resolve_repo("group", "missing").await {
+            Err(ProviderError::Api {
+                provider: ProviderKind::Gitlab,
+                status: Some(404),
+                message,
+            }) => assert_eq!(message, "404 Project Not Found"),
+            other => panic!("expected a 404 API error, got {:?}", other),
+        }
+
+        match gitlab.list_owner_repos("nobody").await {
+            Err(ProviderError::Api {
+                status: Some(404), ..
+            }) => {}
+            other => panic!("expected a 404 API error, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,277 @@
+use crate::executor::ExecutorError;
+use crate::git::{is_ignored, nest_directories, with_ancestors};
+use crate::models::{get_language_from_extension, BranchImpact, DirectoryImpact};
+use git2::{DiffFindOptions, ErrorCode, FileMode, Oid, Patch, Repository};
+use std::collections::HashMap;
+use std::path::Path;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ImpactError {
+    #[error("Branch impact needs a repository analyzed from a local path")]
+    NotLocal,
+    #[error("Unknown revision {0:?}")]
+    UnknownRevision(String),
+    #[error("{base:?} and {head:?} have no common ancestor")]
+    NoMergeBase { base: String, head: String },
+    #[error("Git error: {0}")]
+    Git(#[from] git2::Error),
+    #[error(transparent)]
+    Executor(#[from] ExecutorError),
+}
+
+/// Files and lines changed in a single directory
+#[derive(Default)]
+struct Changes {
+    files: usize,
+    added: u64,
+    removed: u64,
+}
+
+/// Diff `head` against its merge base with `base`, as a pull request from
+/// `head` into `base` would show it.
+///
+/// Renames count as changes to the new path. Only files the analysis counts
+/// are included: those in a known language outside ignored directories such
+/// as `node_modules`. Submodule pointer changes are left out too, so the
+/// result lines up with the repository's directory tree.
+pub fn branch_impact(repo_path: &str, base: &str, head: &str) -> Result<BranchImpact, ImpactError> {
+    let repo = Repository::open(repo_path)?;
+    let base_oid = resolve_commit(&repo, base)?;
+    let head_oid = resolve_commit(&repo, head)?;
+    let merge_base = repo.merge_base(base_oid, head_oid).map_err(|e| {
+        if e.code() == ErrorCode::NotFound {
+            ImpactError::NoMergeBase {
+                base: base.to_string(),
+                head: head.to_string(),
+            }
+        } else {
+            e.into()
+        }
+    })?;
+
+    let base_tree = repo.find_commit(merge_base)?.tree()?;
+    let head_tree = repo.find_commit(head_oid)?.tree()?;
+    let mut diff = repo.diff_tree_to_tree(Some(&base_tree), Some(&head_tree), None)?;
+    diff.find_similar(Some(DiffFindOptions::new().renames(true)))?;
+
+    let mut dir_changes: HashMap<String, Changes> = HashMap::new();
+    for index in 0..diff.deltas().len() {
+        let Some(delta) = diff.get_delta(index) else {
+            continue;
+        };
+        let Some(path) = delta.new_file().path().or(delta.old_file().path()) else {
+            continue;
+        };
+        let is_submodule = [delta.old_file(), delta.new_file()]
+            .iter()
+            .any(|file| file.mode() == FileMode::Commit);
+        let is_code = path
+            .extension()
+            .and_then(|ext| get_language_from_extension(&ext.to_string_lossy()))
+            .is_some();
+        if is_submodule || !is_code || is_ignored(path, Path::new("")) {
+            continue;
+        }
+        let (added, removed) = match Patch::from_diff(&diff, index)? {
+            Some(patch) => {
+                let (_, added, removed) = patch.line_stats()?;
+                (added as u64, removed as u64)
+            }
+            // Binary files have no line counts
+            None => (0, 0),
+        };
+
+        let dir = path
+            .parent()
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let changes = dir_changes.entry(dir).or_default();
+        changes.files += 1;
+        changes.added += added;
+        changes.removed += removed;
+    }
+
+    let directories = build_impact_tree(&dir_changes);
+    Ok(BranchImpact {
+        base: base.to_string(),
+        head: head.to_string(),
+        merge_base: merge_base.to_string(),
+        head_commit: head_oid.to_string(),
+        files_changed: directories.iter().map(|d| d.total_files_changed).sum(),
+        lines_added: directories.iter().map(|d| d.total_lines_added).sum(),
+        lines_removed: directories.iter().map(|d| d.total_lines_removed).sum(),
+        directories,
+    })
+}
+
+fn resolve_commit(repo: &Repository, revision: &str) -> Result<Oid, ImpactError> {
+    let object = repo.revparse_single(revision).map_err(|e| {
+        if matches!(e.code(), ErrorCode::NotFound | ErrorCode::InvalidSpec) {
+            ImpactError::UnknownRevision(revision.to_string())
+        } else {
+            e.into()
+        }
+    })?;
+    Ok(object.peel_to_commit()?.id())
+}
+
+/// Nest changed directories the way `build_directory_tree` nests a
+/// repository's directories, including the ancestors of changed directories
+fn build_impact_tree(dir_changes: &HashMap<String, Changes>) -> Vec<DirectoryImpact> {
+    let nodes: HashMap<String, DirectoryImpact> = with_ancestors(dir_changes.keys())
+        .into_iter()
+        .map(|path| {
+            let name = Path::new(&path)
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_else(|| "(root)".to_string());
+            let changes = dir_changes.get(&path);
+            let node = DirectoryImpact {
+                name,
+                path: path.clone(),
+                files_changed: changes.map_or(0, |c| c.files),
+                lines_added: changes.map_or(0, |c| c.added),
+                lines_removed: changes.map_or(0, |c| c.removed),
+                total_files_changed: 0,
+                total_lines_added: 0,
+                total_lines_removed: 0,
+                children: Vec::new(),
+            };
+            (path, node)
+        })
+        .collect();
+
+    let mut roots = nest_directories(
+        nodes,
+        |node| &mut node.children,
+        |_, node| {
+            sort_by_impact(&mut node.children);
+            node.total_files_changed = node.files_changed;
+            node.total_lines_added = node.lines_added;
+            node.total_lines_removed = node.lines_removed;
+            for child in &node.children {
+                node.total_files_changed += child.total_files_changed;
+                node.total_lines_added += child.total_lines_added;
+                node.total_lines_removed += child.total_lines_removed;
+            }
+        },
+    );
+
+    sort_by_impact(&mut roots);
+    roots
+}
+
+/// Order directories by lines changed including subdirectories, then by path
+fn sort_by_impact(nodes: &mut [DirectoryImpact]) {
+    nodes.sort_by(|a, b| {
+        (b.total_lines_added + b.total_lines_removed)
+            .cmp(&(a.total_lines_added + a.total_lines_removed))
+            .then_with(|| a.path.cmp(&b.path))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{commit_file, git, init_libgit2, init_repo};
+
+    /// `feature` branches off `main`, which then moves on without it
+    fn repository(dir: &Path) -> String {
+        let repo = dir.join("repo");
+        init_repo(&repo);
+        commit_file(&repo, "src/lib.rs", "fn a() {}\nfn b() {}\nfn c() {}\n", 30);
+        commit_file(
+            &repo,
+            "old/util.rs",
+            "fn one() {}\nfn two() {}\nfn three() {}\nfn four() {}\n",
+            30,
+        );
+
+        git(&repo, 20, &["checkout", "--quiet", "-b", "feature"]);
+        commit_file(
+            &repo,
+            "src/lib.rs",
+            "fn a() {}\nfn b() {}\nfn c() {}\nfn d() {}\n",
+            20,
+        );
+        commit_file(&repo, "src/app/main.rs", "fn main() {}\nfn run() {}\n", 20);
+        git(&repo, 20, &["mv", "old", "new"]);
+        git(&repo, 20, &["commit", "--quiet", "-m", "rename old to new"]);
+        commit_file(
+            &repo,
+            "node_modules/pkg/index.js",
+            "module.exports = {};\n",
+            20,
+        );
+        commit_file(&repo, "Cargo.lock", "version = 3\n", 20);
+        commit_file(&repo, "assets/logo.png", "\u{0}PNG\u{1}\u{2}", 20);
+        commit_file(&repo, "src/blob.rs", "\u{0}\u{1}\u{2}\u{3}", 20);
+
+        git(&repo, 10, &["checkout", "--quiet", "main"]);
+        commit_file(&repo, "main_only/extra.rs", "fn extra() {}\n", 10);
+        repo.to_string_lossy().to_string()
+    }
+
+    fn paths(nodes: &[DirectoryImpact]) -> Vec<&str> {
+        nodes.iter().map(|node| node.path.as_str()).collect()
+    }
+
+    #[test]
+    fn reports_code_changed_since_the_merge_base() {
+        init_libgit2();
+        let dir = tempfile::tempdir().unwrap();
+        let repo = repository(dir.path());
+
+        let impact = branch_impact(&repo, "main", "feature").unwrap();
+
+        // Changes on main after the branch point, ignored directories and
+        // files in no known language are left out
+        assert_eq!(paths(&impact.directories), ["src", "new"]);
+        assert_eq!(impact.files_changed, 4);
+        assert_eq!((impact.lines_added, impact.lines_removed), (3, 0));
+
+        // The binary `blob.rs` counts as a changed file without lines
+        let src = &impact.directories[0];
+        assert_eq!(src.files_changed, 2);
+        assert_eq!((src.lines_added, src.total_lines_added), (1, 3));
+        assert_eq!(paths(&src.children), ["src/app"]);
+        assert_eq!(src.children[0].lines_added, 2);
+
+        // The renamed file counts once, at its new path
+        let renamed = &impact.directories[1];
+        assert_eq!(renamed.files_changed, 1);
+        assert_eq!((renamed.lines_added, renamed.lines_removed), (0, 0));
+    }
+
+    #[test]
+    fn compares_against_the_merge_base_not_the_base_tip() {
+        init_libgit2();
+        let dir = tempfile::tempdir().unwrap();
+        let repo = repository(dir.path());
+        let git_repo = Repository::open(&repo).unwrap();
+        let branch_point = git_repo.revparse_single("main~1").unwrap().id().to_string();
+
+        let impact = branch_impact(&repo, "main", "feature").unwrap();
+
+        assert_eq!(impact.merge_base, branch_point);
+        let main_tip = git_repo.revparse_single("main").unwrap().id().to_string();
+        assert_ne!(impact.merge_base, main_tip);
+    }
+
+    #[test]
+    fn rejects_unknown_revisions_and_unrelated_histories() {
+        init_libgit2();
+        let dir = tempfile::tempdir().unwrap();
+        let repo = repository(dir.path());
+
+        let unknown = branch_impact(&repo, "main", "missing");
+        assert!(matches!(unknown, Err(ImpactError::UnknownRevision(rev)) if rev == "missing"));
+
+        let work = Path::new(&repo);
+        git(work, 0, &["checkout", "--quiet", "--orphan", "unrelated"]);
+        commit_file(work, "other.rs", "fn other() {}\n", 0);
+        let unrelated = branch_impact(&repo, "main", "unrelated");
+        assert!(matches!(unrelated, Err(ImpactError::NoMergeBase { .. })));
+    }
+}
@@ -0,0 +1,113 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+/// A running analysis that can be cancelled
+#[derive(Debug, Clone, Serialize)]
+pub struct JobInfo {
+    pub id: String,
+    pub kind: String,
+    pub target: String,
+    pub started_at: DateTime<Utc>,
+}
+
+struct JobEntry {
+    info: JobInfo,
+    token: CancellationToken,
+}
+
+/// Registry of running analyses and their cancellation tokens
+#[derive(Clone, Default)]
+pub struct JobRegistry {
+    jobs: Arc<Mutex<HashMap<String, JobEntry>>>,
+}
+
+/// Registration of a running job.
+///
+/// Dropping the guard unregisters the job and cancels its token. Handlers hold
+/// the guard for the duration of the request, so when the HTTP client goes away
+/// and axum drops the handler future, any blocking work still running for the
+/// request sees the cancellation and stops.
+pub struct JobGuard {
+    registry: JobRegistry,
+    id: String,
+    token: CancellationToken,
+}
+
+impl JobGuard {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+}
+
+impl Drop for JobGuard {
+    fn drop(&mut self) {
+        self.token.cancel();
+        self.registry.jobs.lock().unwrap().remove(&self.id);
+    }
+}
+
+impl JobRegistry {
+    /// Register a job, using the client-supplied id when given.
+    ///
+    /// Returns `None` if a job with the requested id is already running.
+    pub fn start(&self, id: Option<String>, kind: &str, target: &str) -> Option<JobGuard> {
+        let id = id.unwrap_or_else(|| Uuid::new_v4().to_string());
+        let token = CancellationToken::new();
+
+        let mut jobs = self.jobs.lock().unwrap();
+        if jobs.contains_key(&id) {
+            return None;
+        }
+        jobs.insert(
+            id.clone(),
+            JobEntry {
+                info: JobInfo {
+                    id: id.clone(),
+                    kind: kind.to_string(),
+                    target: target.to_string(),
+                    started_at: Utc::now(),
+                },
+                token: token.clone(),
+            },
+        );
+        tracing::debug!("Started {} job {} for {}", kind, id, target);
+
+        Some(JobGuard {
+            registry: self.clone(),
+            id,
+            token,
+        })
+    }
+
+    /// Cancel a running job, returning whether it was found
+    pub fn cancel(&self, id: &str) -> bool {
+        match self.jobs.lock().unwrap().get(id) {
+            Some(entry) => {
+                tracing::info!("Cancelling job {}", id);
+                entry.token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn list(&self) -> Vec<JobInfo> {
+        let mut jobs: Vec<JobInfo> = self
+            .jobs
+            .lock()
+            .unwrap()
+            .values()
+            .map(|entry| entry.info.clone())
+            .collect();
+        jobs.sort_by_key(|job| job.started_at);
+        jobs
+    }
+}
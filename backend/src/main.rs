@@ -1,19 +1,37 @@
 mod api;
+mod cache;
+mod compare;
+mod config;
+mod executor;
 mod git;
 mod github;
+mod gitlab;
+mod impact;
+mod jobs;
 mod models;
+mod provider;
+mod refresh;
+mod remote;
+mod schedule;
+mod sources;
+mod summary;
+#[cfg(test)]
+mod test_support;
+mod tree;
+mod webhooks;
 
 use api::{
-    analyze_github, analyze_local, get_repo, get_repo_tree, health, list_repos, scan_directory,
-    RepoStore,
+    add_source, analyze_local, analyze_remote_owner, analyze_remote_repo, analyze_url, cancel_job,
+    clear_repos, compare_repos, delete_repo, delete_source, generic_webhook, get_cache,
+    get_previous_repo, get_repo, get_repo_impact, get_repo_tree, get_summary, github_webhook,
+    health, list_jobs, list_repos, list_sources, purge_cache, refresh_repo, scan_directory,
+    update_repo, AppState,
 };
 use axum::{
-    routing::{get, post},
+    routing::{delete, get, post},
     Router,
 };
-use std::collections::HashMap;
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use config::Config;
 use tower_http::cors::{Any, CorsLayer};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
@@ -28,8 +46,14 @@ async fn main() {
         )
         .init();
 
+    remote::enable_partial_clone_support();
+
     // Create shared state
-    let store: RepoStore = Arc::new(RwLock::new(HashMap::new()));
+    let config = Config::from_env();
+    let state = AppState::new(&config);
+
+    // Re-analyze tracked sources on their schedules
+    tokio::spawn(sources::run_schedules(state.clone()));
 
     // CORS configuration for development
     let cors = CorsLayer::new()
@@ -40,14 +64,34 @@ async fn main() {
     // Build router
     let app = Router::new()
         .route("/api/health", get(health))
-        .route("/api/repos", get(list_repos))
+        .route("/api/repos", get(list_repos).delete(clear_repos))
         .route("/api/analyze/local", post(analyze_local))
-        .route("/api/analyze/github", post(analyze_github))
+        .route("/api/analyze/remote", post(analyze_remote_repo))
+        .route("/api/analyze/remote/org", post(analyze_remote_owner))
+        // Kept for existing clients; `provider` defaults to GitHub
+        .route("/api/analyze/github", post(analyze_remote_repo))
+        .route("/api/analyze/github/org", post(analyze_remote_owner))
+        .route("/api/analyze/url", post(analyze_url))
         .route("/api/scan", post(scan_directory))
-        .route("/api/repo/{id}", get(get_repo))
+        .route("/api/summary", get(get_summary))
+        .route("/api/compare", get(compare_repos))
+        .route("/api/cache", get(get_cache).delete(purge_cache))
+        .route("/api/jobs", get(list_jobs))
+        .route("/api/jobs/{id}", delete(cancel_job))
+        .route(
+            "/api/repo/{id}",
+            get(get_repo).patch(update_repo).delete(delete_repo),
+        )
+        .route("/api/repo/{id}/refresh", post(refresh_repo))
         .route("/api/repo/{id}/tree", get(get_repo_tree))
+        .route("/api/repo/{id}/previous", get(get_previous_repo))
+        .route("/api/repo/{id}/impact", get(get_repo_impact))
+        .route("/api/sources", get(list_sources).post(add_source))
+        .route("/api/sources/{id}", delete(delete_source))
+        .route("/api/webhooks/github", post(github_webhook))
+        .route("/api/webhooks/generic", post(generic_webhook))
         .layer(cors)
-        .with_state(store);
+        .with_state(state);
 
     let listener = tokio::net::TcpListener::bind("0.0.0.0:3001").await.unwrap();
     tracing::info!("CodeCity backend listening on http://localhost:3001");
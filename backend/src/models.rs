@@ -1,14 +1,100 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RepoAnalysis {
     pub id: String,
     pub name: String,
+    /// Name to show instead of `name`, set through `PATCH /api/repo/{id}`
+    #[serde(default)]
+    pub display_name: Option<String>,
     pub path: String,
     pub age_days: u64,
     pub total_lines: u64,
     pub languages: Vec<LanguageBreakdown>,
     pub directories: Vec<DirectoryNode>,
+    #[serde(default)]
+    pub status: RepoStatus,
+    /// Id of the enclosing repository when this is a submodule
+    #[serde(default)]
+    pub parent_id: Option<String>,
+    /// Metadata from the hosting service for remotely analyzed repositories
+    #[serde(default)]
+    pub remote: Option<RemoteMetadata>,
+    /// Whether lines and ages are estimates from the hosting service's API
+    /// rather than counted from a clone
+    #[serde(default)]
+    pub estimated: bool,
+    /// How a remote repository was cloned, so it can be analyzed again the same way
+    #[serde(default)]
+    pub clone_strategy: Option<CloneStrategy>,
+    /// Whether every directory was given the age of a single commit because
+    /// the history needed to date directories was not available
+    #[serde(default)]
+    pub approximate_directory_ages: bool,
+    /// Whether the hosting service listed only part of the repository's
+    /// files, so lines and directories are undercounted
+    #[serde(default)]
+    pub truncated: bool,
+}
+
+impl RepoAnalysis {
+    /// Language with the most lines
+    pub fn primary_language(&self) -> Option<&str> {
+        self.languages.first().map(|l| l.language.as_str())
+    }
+}
+
+/// Repository metadata reported by a hosting service such as GitHub
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RemoteMetadata {
+    #[serde(default)]
+    pub provider: ProviderKind,
+    pub description: Option<String>,
+    pub default_branch: Option<String>,
+    pub topics: Vec<String>,
+    pub stars: u32,
+    pub archived: bool,
+    pub fork: bool,
+    pub created_at: Option<DateTime<Utc>>,
+    pub pushed_at: Option<DateTime<Utc>>,
+    pub html_url: Option<String>,
+    /// Browse URL that a directory path can be appended to
+    #[serde(default)]
+    pub tree_url: Option<String>,
+}
+
+/// Source hosting service a remote repository is resolved with
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProviderKind {
+    #[default]
+    Github,
+    Gitlab,
+}
+
+impl std::fmt::Display for ProviderKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProviderKind::Github => write!(f, "GitHub"),
+            ProviderKind::Gitlab => write!(f, "GitLab"),
+        }
+    }
+}
+
+/// State of a repository's HEAD at analysis time
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RepoStatus {
+    /// HEAD points to a branch with commits
+    #[default]
+    Ok,
+    /// HEAD is detached from any branch
+    Detached,
+    /// HEAD points to a branch with no commits yet, such as a new orphan branch
+    Unborn,
+    /// The repository has no commits at all
+    Empty,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -24,25 +110,277 @@ pub struct DirectoryNode {
     pub name: String,
     pub path: String,
     pub age_days: u64,
+    /// Lines in files directly inside the directory
     pub lines: u64,
     pub languages: Vec<LanguageBreakdown>,
+    /// Lines in the directory and all of its subdirectories
+    pub total_lines: u64,
+    pub total_languages: Vec<LanguageBreakdown>,
     pub children: Vec<DirectoryNode>,
+    /// Number of children left out of `children` by a depth limit
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub child_count: Option<usize>,
+    /// Number of directories merged into this node by `top` or `min_lines`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub folded: Option<usize>,
+}
+
+/// Query parameters of `GET /api/repo/{id}/tree`
+#[derive(Debug, Default, Deserialize)]
+pub struct TreeQuery {
+    /// Only the directory at this path and everything below it
+    pub path: Option<String>,
+    /// Number of levels to return; deeper children are left out
+    pub depth: Option<usize>,
+    /// Fold directories with fewer lines, counting subdirectories, into "(other)"
+    pub min_lines: Option<u64>,
+    /// Keep the largest N directories at each level, folding the rest into "(other)"
+    pub top: Option<usize>,
+    /// Collapse chains of directories that only hold a single subdirectory
+    #[serde(default)]
+    pub compress: bool,
+}
+
+/// Field `GET /api/repos` is sorted by
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RepoSort {
+    #[default]
+    Name,
+    Lines,
+    Age,
+    /// Primary language
+    Language,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    Asc,
+    Desc,
 }
 
 #[derive(Debug, Deserialize)]
+pub struct ListReposQuery {
+    #[serde(default)]
+    pub offset: usize,
+    pub limit: Option<usize>,
+    #[serde(default)]
+    pub sort: RepoSort,
+    /// Names and languages sort ascending, lines and ages descending unless given
+    pub order: Option<SortOrder>,
+    /// Only repositories with this primary language, ignoring case
+    pub language: Option<String>,
+    /// Only repositories whose name contains this, ignoring case
+    pub name: Option<String>,
+    pub min_lines: Option<u64>,
+    pub max_lines: Option<u64>,
+    /// Comma-separated extras; `tree` adds each repository's `directories`
+    pub include: Option<String>,
+}
+
+/// A repository as listed by `GET /api/repos`, without its directory tree
+/// unless requested
+#[derive(Debug, Serialize)]
+pub struct RepoSummary {
+    pub id: String,
+    pub name: String,
+    pub display_name: Option<String>,
+    pub path: String,
+    pub age_days: u64,
+    pub total_lines: u64,
+    pub primary_language: Option<String>,
+    pub languages: Vec<LanguageBreakdown>,
+    pub status: RepoStatus,
+    pub parent_id: Option<String>,
+    pub remote: Option<RemoteMetadata>,
+    pub estimated: bool,
+    pub approximate_directory_ages: bool,
+    pub truncated: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub directories: Option<Vec<DirectoryNode>>,
+}
+
+impl RepoSummary {
+    pub fn new(repo: &RepoAnalysis, include_tree: bool) -> Self {
+        Self {
+            id: repo.id.clone(),
+            name: repo.name.clone(),
+            display_name: repo.display_name.clone(),
+            path: repo.path.clone(),
+            age_days: repo.age_days,
+            total_lines: repo.total_lines,
+            primary_language: repo.primary_language().map(str::to_string),
+            languages: repo.languages.clone(),
+            status: repo.status,
+            parent_id: repo.parent_id.clone(),
+            remote: repo.remote.clone(),
+            estimated: repo.estimated,
+            approximate_directory_ages: repo.approximate_directory_ages,
+            truncated: repo.truncated,
+            directories: include_tree.then(|| repo.directories.clone()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct RepoListResponse {
+    pub repos: Vec<RepoSummary>,
+    /// Repositories matching the filters, across all pages
+    pub total: usize,
+    pub offset: usize,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AnalyzeLocalRequest {
     pub path: String,
+    /// Optional client-chosen id for cancelling the analysis
+    #[serde(default, skip_serializing)]
+    pub job_id: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
-pub struct AnalyzeGithubRequest {
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnalyzeRemoteRequest {
+    /// Hosting service of the repository; `github` unless given
+    #[serde(default)]
+    pub provider: ProviderKind,
+    /// User, organization or group; GitLab subgroups are written `group/subgroup`
     pub owner: String,
     pub repo: String,
+    /// Clone the repository, or estimate from the GitHub API without cloning
+    #[serde(default)]
+    pub mode: AnalysisMode,
+    /// Provider token overriding the server's configured token
+    #[serde(default, skip_serializing)]
+    pub token: Option<String>,
+    /// How to clone the repository; `full` unless given
+    #[serde(default)]
+    pub clone_strategy: CloneStrategy,
+    /// Optional client-chosen id for cancelling the analysis
+    #[serde(default, skip_serializing)]
+    pub job_id: Option<String>,
+}
+
+/// Analyze every repository owned by an organization, group or user
+#[derive(Debug, Deserialize)]
+pub struct AnalyzeRemoteOwnerRequest {
+    /// Hosting service of the owner; `github` unless given
+    #[serde(default)]
+    pub provider: ProviderKind,
+    pub owner: String,
+    #[serde(default)]
+    pub include_archived: bool,
+    #[serde(default)]
+    pub include_forks: bool,
+    /// Only analyze repositories with at least one of these topics
+    #[serde(default)]
+    pub topics: Vec<String>,
+    /// Only analyze repositories whose primary language is one of these
+    #[serde(default)]
+    pub languages: Vec<String>,
+    /// Maximum number of repositories to analyze
+    #[serde(default)]
+    pub max_repos: Option<usize>,
+    /// Provider token overriding the server's configured token
+    #[serde(default)]
+    pub token: Option<String>,
+    /// How to clone the repository; `full` unless given
+    #[serde(default)]
+    pub clone_strategy: CloneStrategy,
+    /// Optional client-chosen id for cancelling the analysis
+    #[serde(default)]
+    pub job_id: Option<String>,
+}
+
+/// How a remote repository is analyzed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnalysisMode {
+    /// Clone the repository and count every file
+    #[default]
+    Clone,
+    /// Estimate lines from blob sizes in the Git Trees API and age from the
+    /// commits API; directory ages are the repository's age. GitHub only
+    Api,
+}
+
+/// How a remote repository is cloned for analysis
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CloneStrategy {
+    /// Every commit, tree and blob of the default branch
+    #[default]
+    Full,
+    /// Commits and trees, but only the blobs checked out at HEAD
+    Blobless,
+    /// Commits only; trees and blobs are fetched for HEAD. Directory ages
+    /// cannot be computed and fall back to the latest commit
+    Treeless,
+}
+
+impl CloneStrategy {
+    /// Partial clone filter passed to `git clone --filter`
+    pub fn filter(self) -> Option<&'static str> {
+        match self {
+            CloneStrategy::Full => None,
+            CloneStrategy::Blobless => Some("blob:none"),
+            CloneStrategy::Treeless => Some("tree:0"),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AnalyzeUrlRequest {
+    /// Any https, ssh, git or `file://` URL git can clone, or `user@host:path`
+    pub url: String,
+    /// Display name; derived from the URL when omitted
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Access token sent when cloning over HTTPS
+    #[serde(default)]
+    pub token: Option<String>,
+    /// Username sent with the token; hosts such as Bitbucket require a specific one
+    #[serde(default)]
+    pub username: Option<String>,
+    /// How to clone the repository; `full` unless given
+    #[serde(default)]
+    pub clone_strategy: CloneStrategy,
+    /// Optional client-chosen id for cancelling the analysis
+    #[serde(default)]
+    pub job_id: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
+pub struct PurgeCacheQuery {
+    /// Only remove the cached clone of this URL
+    pub url: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ScanDirectoryRequest {
     pub path: String,
+    /// Analyze submodules as nested repositories linked to their parent;
+    /// otherwise their files count towards the parent
+    #[serde(default)]
+    pub include_submodules: bool,
+    /// Maximum directory depth below `path` to look for repositories
+    #[serde(default)]
+    pub max_depth: Option<usize>,
+    /// Glob patterns, relative to `path`, that repositories must match
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// Glob patterns, relative to `path`, for directories to skip
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    #[serde(default)]
+    pub follow_symlinks: bool,
+    /// Maximum number of repositories to analyze
+    #[serde(default)]
+    pub max_repos: Option<usize>,
+    /// Optional client-chosen id for cancelling the scan
+    #[serde(default, skip_serializing)]
+    pub job_id: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -50,6 +388,175 @@ pub struct ScanDirectoryResponse {
     pub repos: Vec<RepoAnalysis>,
     pub total_found: usize,
     pub total_analyzed: usize,
+    /// Whether `max_repos` stopped some found repositories from being analyzed
+    pub truncated: bool,
+    pub failures: Vec<ScanFailure>,
+    pub summary: PortfolioSummary,
+}
+
+/// Changes to a stored repository
+#[derive(Debug, Deserialize)]
+pub struct UpdateRepoRequest {
+    /// New display name; `null` or an empty string restores the analyzed name
+    pub display_name: Option<String>,
+}
+
+/// Response to an accepted push webhook
+#[derive(Debug, Serialize)]
+pub struct WebhookResponse {
+    /// Ids of the stored repositories queued for re-analysis
+    pub queued: Vec<String>,
+}
+
+/// Why a repository could not be analyzed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureKind {
+    InvalidPath,
+    NotARepository,
+    Empty,
+    Corrupt,
+    Unreadable,
+    CloneFailed,
+    AuthFailed,
+    RemoteNotFound,
+    NetworkError,
+    Other,
+}
+
+/// A repository that could not be analyzed during a scan
+#[derive(Debug, Clone, Serialize)]
+pub struct ScanFailure {
+    pub path: String,
+    pub error_kind: FailureKind,
+    pub message: String,
+}
+
+/// Lightweight reference to an analyzed repository used in summaries
+#[derive(Debug, Clone, Serialize)]
+pub struct RepoRef {
+    pub id: String,
+    pub name: String,
+    pub path: String,
+    pub age_days: u64,
+    pub total_lines: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LanguageRepoCount {
+    pub language: String,
+    pub repos: usize,
+    pub color: String,
+}
+
+/// Portfolio-wide totals across a set of analyzed repositories
+#[derive(Debug, Clone, Serialize)]
+pub struct PortfolioSummary {
+    pub total_repos: usize,
+    pub total_lines: u64,
+    pub languages: Vec<LanguageBreakdown>,
+    pub oldest: Option<RepoRef>,
+    pub newest: Option<RepoRef>,
+    pub largest: Vec<RepoRef>,
+    pub primary_languages: Vec<LanguageRepoCount>,
+    pub failures: Vec<ScanFailure>,
+}
+
+/// Query parameters of `GET /api/compare`
+#[derive(Debug, Deserialize)]
+pub struct CompareQuery {
+    /// Id of the earlier analysis; defaults to the analysis `head` replaced
+    pub base: Option<String>,
+    pub head: String,
+}
+
+/// Change in the lines of one language between two analyses
+#[derive(Debug, Clone, Serialize)]
+pub struct LanguageDelta {
+    pub language: String,
+    pub color: String,
+    pub base_lines: u64,
+    pub head_lines: u64,
+    pub lines_delta: i64,
+    /// Change in the language's share of lines, in percentage points
+    pub percentage_delta: f32,
+}
+
+/// A directory added, removed or changed between two analyses, counting its
+/// subdirectories
+#[derive(Debug, Clone, Serialize)]
+pub struct DirectoryDelta {
+    pub path: String,
+    pub name: String,
+    pub base_lines: u64,
+    pub head_lines: u64,
+    pub lines_delta: i64,
+    /// `None` for added directories
+    pub base_age_days: Option<u64>,
+    /// `None` for removed directories
+    pub head_age_days: Option<u64>,
+    pub languages: Vec<LanguageDelta>,
+}
+
+/// Differences between two analyses, aligned by directory path
+#[derive(Debug, Clone, Serialize)]
+pub struct RepoComparison {
+    pub base: RepoRef,
+    pub head: RepoRef,
+    pub lines_delta: i64,
+    pub age_delta: i64,
+    pub languages: Vec<LanguageDelta>,
+    pub added: Vec<DirectoryDelta>,
+    pub removed: Vec<DirectoryDelta>,
+    /// Directories whose lines or languages differ
+    pub changed: Vec<DirectoryDelta>,
+    /// Number of directories in both analyses with the same lines and languages
+    pub unchanged: usize,
+}
+
+/// Query parameters of `GET /api/repo/{id}/impact`
+#[derive(Debug, Deserialize)]
+pub struct ImpactQuery {
+    /// Branch or other revision the changes would be merged into
+    pub base: String,
+    /// Revision with the changes; defaults to the checked out `HEAD`
+    #[serde(default = "default_impact_head")]
+    pub head: String,
+}
+
+fn default_impact_head() -> String {
+    "HEAD".to_string()
+}
+
+/// Changes a branch makes to one directory, at the same path as its `DirectoryNode`
+#[derive(Debug, Clone, Serialize)]
+pub struct DirectoryImpact {
+    pub name: String,
+    pub path: String,
+    /// Files directly inside the directory that the branch changes
+    pub files_changed: usize,
+    pub lines_added: u64,
+    pub lines_removed: u64,
+    /// Changes in the directory and all of its subdirectories
+    pub total_files_changed: usize,
+    pub total_lines_added: u64,
+    pub total_lines_removed: u64,
+    pub children: Vec<DirectoryImpact>,
+}
+
+/// Changes on `head` since it diverged from `base`
+#[derive(Debug, Clone, Serialize)]
+pub struct BranchImpact {
+    pub base: String,
+    pub head: String,
+    /// Commit `head` is compared against
+    pub merge_base: String,
+    pub head_commit: String,
+    pub files_changed: usize,
+    pub lines_added: u64,
+    pub lines_removed: u64,
+    /// Changed directories only, nested like the repository's directory tree
+    pub directories: Vec<DirectoryImpact>,
 }
 
 #[derive(Debug, Serialize)]
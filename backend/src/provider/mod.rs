@@ -0,0 +1,466 @@
+use crate::cache::CloneCache;
+use crate::config::Config;
+use crate::executor::{AnalysisExecutor, ExecutorError};
+use crate::git::{DirectoryAnalysis, GitError};
+use crate::github::{estimate_github_repo, GithubClientConfig};
+use crate::gitlab::GitlabClientConfig;
+use crate::models::{
+    AnalysisMode, AnalyzeRemoteOwnerRequest, AnalyzeRemoteRequest, CloneStrategy, FailureKind,
+    ProviderKind, RemoteMetadata, RepoAnalysis, ScanFailure,
+};
+use crate::remote::{analyze_remote, RemoteCredentials, RemoteError};
+use async_trait::async_trait;
+use std::sync::Arc;
+use thiserror::Error;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use tokio_util::sync::CancellationToken;
+
+/// Maximum number of clones running at once when analyzing an organization
+const MAX_CONCURRENT_CLONES: usize = 4;
+
+#[derive(Error, Debug)]
+pub enum ProviderError {
+    #[error("{provider} API error: {message}")]
+    Api {
+        provider: ProviderKind,
+        status: Option<u16>,
+        message: String,
+    },
+    #[error("{0} is not supported by {1}")]
+    Unsupported(&'static str, ProviderKind),
+    #[error(transparent)]
+    Remote(#[from] RemoteError),
+    #[error("Git analysis error: {0}")]
+    Analysis(#[from] GitError),
+    #[error("Analysis was cancelled")]
+    Cancelled,
+}
+
+impl ProviderError {
+    /// Wrap an error from the GitHub REST API
+    pub fn github(e: octocrab::Error) -> Self {
+        let (status, message) = match &e {
+            octocrab::Error::GitHub { source, .. } => {
+                (Some(source.status_code.as_u16()), source.message.clone())
+            }
+            _ => (None, e.to_string()),
+        };
+        ProviderError::Api {
+            provider: ProviderKind::Github,
+            status,
+            message,
+        }
+    }
+
+    /// Classify the error for reporting failed repositories
+    pub fn kind(&self) -> FailureKind {
+        match self {
+            ProviderError::Api {
+                status: Some(404), ..
+            } => FailureKind::RemoteNotFound,
+            ProviderError::Api {
+                status: Some(401 | 403),
+                ..
+            } => FailureKind::AuthFailed,
+            ProviderError::Analysis(e) => e.kind(),
+            ProviderError::Remote(e) => e.kind(),
+            ProviderError::Api { .. }
+            | ProviderError::Unsupported(..)
+            | ProviderError::Cancelled => FailureKind::Other,
+        }
+    }
+}
+
+/// A repository as described by its hosting provider
+#[derive(Debug, Clone)]
+pub struct RemoteRepo {
+    /// `owner/name`, or `group/subgroup/name` on GitLab
+    pub full_name: String,
+    pub clone_url: Option<String>,
+    /// Primary language, when the provider includes it in listings
+    pub language: Option<String>,
+    pub metadata: RemoteMetadata,
+}
+
+/// A source hosting service that repositories can be resolved and cloned from
+#[async_trait]
+pub trait RemoteProvider: Send + Sync {
+    /// Look up a single repository
+    async fn resolve_repo(&self, owner: &str, repo: &str) -> Result<RemoteRepo, ProviderError>;
+
+    /// List every repository of an organization or group, falling back to a user
+    async fn list_owner_repos(&self, owner: &str) -> Result<Vec<RemoteRepo>, ProviderError>;
+
+    /// Primary language of a repository, looked up if listings leave it out
+    async fn primary_language(&self, repo: &RemoteRepo) -> Result<Option<String>, ProviderError> {
+        Ok(repo.language.clone())
+    }
+
+    /// Credentials for cloning over HTTPS
+    fn clone_credentials(&self) -> RemoteCredentials;
+
+    /// Browse URL that a directory path can be appended to
+    fn tree_url(&self, repo: &RemoteRepo) -> Option<String>;
+}
+
+/// Configured endpoints and credentials of every supported provider
+#[derive(Clone)]
+pub struct Providers {
+    pub github: GithubClientConfig,
+    pub gitlab: GitlabClientConfig,
+}
+
+impl Providers {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            github: GithubClientConfig::from_config(config),
+            gitlab: GitlabClientConfig::from_config(config),
+        }
+    }
+
+    /// The provider of a request, using its token in place of the configured one
+    pub fn get(&self, kind: ProviderKind, token: Option<String>) -> Arc<dyn RemoteProvider> {
+        match kind {
+            ProviderKind::Github => Arc::new(self.github.with_token(token)),
+            ProviderKind::Gitlab => Arc::new(self.gitlab.with_token(token)),
+        }
+    }
+}
+
+/// Analyze a repository as described by an analyze request
+pub async fn analyze_remote_request(
+    providers: &Providers,
+    request: &AnalyzeRemoteRequest,
+    cache: &CloneCache,
+    executor: &AnalysisExecutor,
+    cancel: &CancellationToken,
+) -> Result<RepoAnalysis, ProviderError> {
+    match (request.mode, request.provider) {
+        (AnalysisMode::Clone, kind) => {
+            let provider = providers.get(kind, request.token.clone());
+            analyze_provider_repo(
+                provider.as_ref(),
+                &request.owner,
+                &request.repo,
+                request.clone_strategy,
+                cache,
+                executor,
+                cancel,
+            )
+            .await
+        }
+        (AnalysisMode::Api, ProviderKind::Github) => {
+            let github = providers.github.with_token(request.token.clone());
+            estimate_github_repo(&request.owner, &request.repo, &github, cancel).await
+        }
+        (AnalysisMode::Api, kind) => Err(ProviderError::Unsupported("API mode", kind)),
+    }
+}
+
+/// Resolve a repository with its provider, then clone and analyze it
+pub async fn analyze_provider_repo(
+    provider: &dyn RemoteProvider,
+    owner: &str,
+    repo: &str,
+    strategy: CloneStrategy,
+    cache: &CloneCache,
+    executor: &AnalysisExecutor,
+    cancel: &CancellationToken,
+) -> Result<RepoAnalysis, ProviderError> {
+    let remote_repo = tokio::select! {
+        result = provider.resolve_repo(owner, repo) => result?,
+        _ = cancel.cancelled() => return Err(ProviderError::Cancelled),
+    };
+    analyze_remote_repo(provider, &remote_repo, strategy, cache, executor, cancel).await
+}
+
+/// Clone a resolved repository and analyze it
+async fn analyze_remote_repo(
+    provider: &dyn RemoteProvider,
+    remote_repo: &RemoteRepo,
+    strategy: CloneStrategy,
+    cache: &CloneCache,
+    executor: &AnalysisExecutor,
+    cancel: &CancellationToken,
+) -> Result<RepoAnalysis, ProviderError> {
+    let clone_url = remote_repo
+        .clone_url
+        .as_deref()
+        .ok_or_else(|| RemoteError::Clone("No clone URL available".to_string()))?;
+
+    let mut analysis = analyze_remote(
+        clone_url,
+        &remote_repo.full_name,
+        &provider.clone_credentials(),
+        strategy,
+        cache,
+        executor,
+        cancel,
+    )
+    .await?;
+
+    let mut metadata = remote_repo.metadata.clone();
+    metadata.tree_url = provider.tree_url(remote_repo);
+    analysis.remote = Some(metadata);
+
+    Ok(analysis)
+}
+
+fn matches_owner_filter(repo: &RemoteRepo, request: &AnalyzeRemoteOwnerRequest) -> bool {
+    let metadata = &repo.metadata;
+    if !request.include_archived && metadata.archived {
+        return false;
+    }
+    if !request.include_forks && metadata.fork {
+        return false;
+    }
+    if !request.topics.is_empty() && !request.topics.iter().any(|t| metadata.topics.contains(t)) {
+        return false;
+    }
+    true
+}
+
+fn matches_language_filter(language: Option<&str>, request: &AnalyzeRemoteOwnerRequest) -> bool {
+    request.languages.is_empty()
+        || language.is_some_and(|language| {
+            request
+                .languages
+                .iter()
+                .any(|l| l.eq_ignore_ascii_case(language))
+        })
+}
+
+/// Analyze every repository of an organization, group or user in parallel
+pub async fn analyze_provider_owner(
+    provider: Arc<dyn RemoteProvider>,
+    request: &AnalyzeRemoteOwnerRequest,
+    cache: &CloneCache,
+    executor: &AnalysisExecutor,
+    cancel: &CancellationToken,
+) -> Result<DirectoryAnalysis, ProviderError> {
+    let list = async {
+        let mut repos = Vec::new();
+        for repo in provider.list_owner_repos(&request.owner).await? {
+            if !matches_owner_filter(&repo, request) {
+                continue;
+            }
+            if !request.languages.is_empty() {
+                let language = provider.primary_language(&repo).await?;
+                if !matches_language_filter(language.as_deref(), request) {
+                    continue;
+                }
+            }
+            repos.push(repo);
+        }
+        Ok::<_, ProviderError>(repos)
+    };
+    let mut repos = tokio::select! {
+        result = list => result?,
+        _ = cancel.cancelled() => return Err(ProviderError::Cancelled),
+    };
+    let total_found = repos.len();
+
+    let truncated = request
+        .max_repos
+        .is_some_and(|max_repos| repos.len() > max_repos);
+    if let Some(max_repos) = request.max_repos {
+        repos.truncate(max_repos);
+    }
+
+    tracing::info!(
+        "Found {} repositories for {}, analyzing {} in parallel...",
+        total_found,
+        request.owner,
+        repos.len()
+    );
+
+    // Cloning is network bound, so it is limited separately from analysis
+    let clone_permits = Arc::new(Semaphore::new(MAX_CONCURRENT_CLONES));
+    let mut tasks = JoinSet::new();
+    for remote_repo in repos {
+        // Failures are keyed like analyses, by clone URL
+        let path = remote_repo
+            .clone_url
+            .clone()
+            .unwrap_or_else(|| remote_repo.full_name.clone());
+        let provider = provider.clone();
+        let strategy = request.clone_strategy;
+        let clone_permits = clone_permits.clone();
+        let cache = cache.clone();
+        let executor = executor.clone();
+        let cancel = cancel.clone();
+        tasks.spawn(async move {
+            let _permit = clone_permits.acquire_owned().await;
+            let result = analyze_remote_repo(
+                provider.as_ref(),
+                &remote_repo,
+                strategy,
+                &cache,
+                &executor,
+                &cancel,
+            )
+            .await;
+            (path, result)
+        });
+    }
+
+    let mut analyses = Vec::new();
+    let mut failures = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        let (path, result) = joined.map_err(|e| GitError::from(ExecutorError::from(e)))?;
+        match result {
+            Ok(analysis) => analyses.push(analysis),
+            Err(e) => {
+                tracing::warn!("Failed to analyze {}: {}", path, e);
+                failures.push(ScanFailure {
+                    path,
+                    error_kind: e.kind(),
+                    message: e.to_string(),
+                });
+            }
+        }
+    }
+
+    if cancel.is_cancelled() {
+        return Err(ProviderError::Cancelled);
+    }
+
+    // Tasks complete in any order; report results in a stable one
+    analyses.sort_by(|a, b| a.path.cmp(&b.path));
+    failures.sort_by(|a, b| a.path.cmp(&b.path));
+
+    tracing::info!(
+        "Completed {}: {} succeeded, {} failed",
+        request.owner,
+        analyses.len(),
+        failures.len()
+    );
+
+    Ok(DirectoryAnalysis {
+        repos: analyses,
+        failures,
+        total_found,
+        truncated,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{commit_file, git, init_libgit2, init_repo, serve, test_config};
+    use axum::extract::Path;
+    use axum::http::StatusCode;
+    use axum::routing::get;
+    use axum::{Json, Router};
+    use serde_json::json;
+
+    /// Error response for a repository named after the status it fails with
+    async fn failing_repo(Path(path): Path<Vec<String>>) -> (StatusCode, Json<serde_json::Value>) {
+        let name = path.last().unwrap().rsplit('/').next().unwrap();
+        let status = match name {
+            "unauthorized" => StatusCode::UNAUTHORIZED,
+            "forbidden" => StatusCode::FORBIDDEN,
+            _ => StatusCode::NOT_FOUND,
+        };
+        let message = status.canonical_reason().unwrap();
+        (
+            status,
+            Json(json!({ "message": message, "documentation_url": "https://docs.example" })),
+        )
+    }
+
+    /// Both providers pointed at one stand-in that only returns errors
+    async fn failing_providers() -> Providers {
+        let url = serve(
+            Router::new()
+                .route("/repos/{owner}/{repo}", get(failing_repo))
+                .route("/api/v4/projects/{id}", get(failing_repo)),
+        )
+        .await;
+        let mut config = test_config(std::env::temp_dir());
+        config.github_api_url = Some(url.clone());
+        config.gitlab_url = url;
+        Providers::from_config(&config)
+    }
+
+    #[tokio::test]
+    async fn classifies_api_errors_of_both_providers() {
+        let providers = failing_providers().await;
+
+        for kind in [ProviderKind::Github, ProviderKind::Gitlab] {
+            let provider = providers.get(kind, Some("token".to_string()));
+            for (repo, status, failure) in [
+                ("missing", 404, FailureKind::RemoteNotFound),
+                ("unauthorized", 401, FailureKind::AuthFailed),
+                ("forbidden", 403, FailureKind::AuthFailed),
+            ] {
+                let error = provider.resolve_repo("owner", repo).await.unwrap_err();
+                match &error {
+                    ProviderError::Api {
+                        provider,
+                        status: Some(code),
+                        ..
+                    } => {
+                        assert_eq!(*provider, kind);
+                        assert_eq!(*code, status, "{} {}", kind, repo);
+                    }
+                    other => panic!("expected an API error, got {:?}", other),
+                }
+                assert_eq!(error.kind(), failure, "{} {}", kind, repo);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn reports_owner_results_in_path_order() {
+        init_libgit2();
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["a", "c"] {
+            let work = dir.path().join(name);
+            init_repo(&work);
+            commit_file(&work, "main.rs", "fn main() {}\n", 10);
+            git(
+                dir.path(),
+                0,
+                &["clone", "--quiet", "--bare", name, &format!("{}.git", name)],
+            );
+        }
+        // Listed out of order, with `b` missing from disk so its clone fails
+        let projects: Vec<serde_json::Value> = ["c", "b", "a"]
+            .iter()
+            .map(|name| {
+                json!({
+                    "path_with_namespace": format!("group/{}", name),
+                    "http_url_to_repo": format!("file://{}/{}.git", dir.path().display(), name),
+                })
+            })
+            .collect();
+        let url = serve(Router::new().route(
+            "/api/v4/groups/{group}/projects",
+            get(move || async move { Json(projects) }),
+        ))
+        .await;
+        let mut config = test_config(dir.path().join("cache"));
+        config.gitlab_url = url;
+        let provider = Providers::from_config(&config).get(ProviderKind::Gitlab, None);
+        let request: AnalyzeRemoteOwnerRequest =
+            serde_json::from_value(json!({ "provider": "gitlab", "owner": "group" })).unwrap();
+
+        let analysis = analyze_provider_owner(
+            provider,
+            &request,
+            &CloneCache::new(&config),
+            &AnalysisExecutor::new(&config),
+            &CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+
+        let url = |name: &str| format!("file://{}/{}.git", dir.path().display(), name);
+        let analyzed: Vec<&str> = analysis.repos.iter().map(|r| r.path.as_str()).collect();
+        assert_eq!(analyzed, [url("a"), url("c")]);
+        let failed: Vec<&str> = analysis.failures.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(failed, [url("b")]);
+    }
+}
@@ -0,0 +1,70 @@
+use crate::cache::CloneCache;
+use crate::executor::AnalysisExecutor;
+use crate::git::{analyze_repository, GitError};
+use crate::github::estimate_github_repo;
+use crate::models::{ProviderKind, RepoAnalysis};
+use crate::provider::{analyze_provider_repo, ProviderError, Providers};
+use crate::remote::{analyze_remote, RemoteCredentials};
+use std::path::Path;
+use tokio_util::sync::CancellationToken;
+
+/// Analyze a stored repository again the same way it was first analyzed.
+///
+/// The new analysis keeps the previous id and display name. Remote
+/// repositories are fetched with the configured provider tokens, since tokens
+/// passed with the original request are not stored.
+pub async fn reanalyze(
+    previous: &RepoAnalysis,
+    providers: &Providers,
+    cache: &CloneCache,
+    executor: &AnalysisExecutor,
+    cancel: &CancellationToken,
+) -> Result<RepoAnalysis, ProviderError> {
+    let owner_repo = previous.name.rsplit_once('/');
+    let mut analysis = match (&previous.remote, owner_repo, previous.clone_strategy) {
+        // Resolved through a provider, so `name` is its full name
+        (Some(remote), Some((owner, repo)), strategy) => {
+            if previous.estimated && remote.provider == ProviderKind::Github {
+                estimate_github_repo(owner, repo, &providers.github, cancel).await?
+            } else {
+                let provider = providers.get(remote.provider, None);
+                analyze_provider_repo(
+                    provider.as_ref(),
+                    owner,
+                    repo,
+                    strategy.unwrap_or_default(),
+                    cache,
+                    executor,
+                    cancel,
+                )
+                .await?
+            }
+        }
+        // Cloned from a URL, which is kept redacted in `path`
+        (_, _, Some(strategy)) => {
+            analyze_remote(
+                &previous.path,
+                &previous.name,
+                &RemoteCredentials::default(),
+                strategy,
+                cache,
+                executor,
+                cancel,
+            )
+            .await?
+        }
+        _ => {
+            let path = previous.path.clone();
+            let cancel = cancel.clone();
+            executor
+                .run(move || analyze_repository(Path::new(&path), false, &cancel))
+                .await
+                .map_err(GitError::from)??
+        }
+    };
+
+    analysis.id = previous.id.clone();
+    analysis.parent_id = previous.parent_id.clone();
+    analysis.display_name = previous.display_name.clone();
+    Ok(analysis)
+}
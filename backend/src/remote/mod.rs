@@ -0,0 +1,694 @@
+use crate::cache::{CacheCheckout, CloneCache};
+use crate::executor::AnalysisExecutor;
+use crate::git::{analyze_repository, GitError};
+use crate::models::{CloneStrategy, FailureKind, RepoAnalysis};
+use base64::Engine;
+use git2::build::CheckoutBuilder;
+use git2::{
+    AutotagOption, Cred, CredentialType, Direction, ErrorClass, ErrorCode, FetchOptions,
+    RemoteCallbacks, Repository,
+};
+use std::path::{Path, PathBuf};
+use std::process::Output;
+use std::sync::Once;
+use thiserror::Error;
+use tokio::process::Command;
+use tokio_util::sync::CancellationToken;
+use url::Url;
+
+/// URL schemes accepted for remote analysis
+const SUPPORTED_SCHEMES: &[&str] = &["https", "http", "ssh", "git", "file"];
+
+/// Private keys tried, in order, when no SSH agent accepts the connection
+const DEFAULT_SSH_KEYS: &[&str] = &["id_ed25519", "id_ecdsa", "id_rsa"];
+
+/// Username sent with a token when none is given; GitHub requires this one
+/// and GitLab and Gitea accept any username alongside an access token
+const DEFAULT_TOKEN_USERNAME: &str = "x-access-token";
+
+#[derive(Error, Debug)]
+pub enum RemoteError {
+    #[error("Invalid repository URL: {0}")]
+    InvalidUrl(String),
+    #[error("Authentication failed: {0}")]
+    Auth(String),
+    #[error("Repository not found: {0}")]
+    NotFound(String),
+    #[error("Network error: {0}")]
+    Network(String),
+    #[error("Git clone failed: {0}")]
+    Clone(String),
+    #[error("Git analysis error: {0}")]
+    Analysis(#[from] GitError),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Analysis was cancelled")]
+    Cancelled,
+}
+
+impl RemoteError {
+    /// Classify the error for reporting failed repositories
+    pub fn kind(&self) -> FailureKind {
+        match self {
+            RemoteError::InvalidUrl(_) => FailureKind::InvalidPath,
+            RemoteError::Auth(_) => FailureKind::AuthFailed,
+            RemoteError::NotFound(_) => FailureKind::RemoteNotFound,
+            RemoteError::Network(_) => FailureKind::NetworkError,
+            RemoteError::Clone(_) => FailureKind::CloneFailed,
+            RemoteError::Analysis(e) => e.kind(),
+            RemoteError::Io(_) => FailureKind::Unreadable,
+            RemoteError::Cancelled => FailureKind::Other,
+        }
+    }
+
+    /// Categorize a failed clone or fetch
+    fn from_git(e: git2::Error, cancel: &CancellationToken) -> Self {
+        if cancel.is_cancelled() {
+            return RemoteError::Cancelled;
+        }
+        match (e.code(), e.class()) {
+            (ErrorCode::Auth, _) => RemoteError::Auth(e.message().to_string()),
+            (ErrorCode::Certificate, _) => RemoteError::Network(e.message().to_string()),
+            (_, class) => match Self::from_message(e.message()) {
+                RemoteError::Clone(message)
+                    if matches!(class, ErrorClass::Net | ErrorClass::Ssl | ErrorClass::Os) =>
+                {
+                    RemoteError::Network(message)
+                }
+                error => error,
+            },
+        }
+    }
+
+    /// Categorize a failure from its error message
+    fn from_message(stderr: &str) -> Self {
+        const AUTH: &[&str] = &[
+            "authentication failed",
+            "could not read username",
+            "could not read password",
+            "terminal prompts disabled",
+            "permission denied",
+            "access denied",
+            "http basic",
+            "authentication replays",
+            "status code: 401",
+            "status code: 403",
+        ];
+        const NOT_FOUND: &[&str] = &[
+            "repository not found",
+            "not found",
+            "does not appear to be a git repository",
+            "does not exist",
+            "could not find repository",
+            "failed to resolve path",
+            "status code: 404",
+        ];
+        const NETWORK: &[&str] = &[
+            "could not resolve host",
+            "failed to resolve address",
+            "connection refused",
+            "connection timed out",
+            "operation timed out",
+            "failed to connect",
+            "network is unreachable",
+            "connection reset",
+            "certificate",
+        ];
+
+        // Drop the git CLI's progress chatter and keep the actual error lines
+        let message = stderr
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with("Cloning into"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let lower = message.to_lowercase();
+        let matches = |patterns: &[&str]| patterns.iter().any(|p| lower.contains(p));
+        if matches(AUTH) {
+            RemoteError::Auth(message)
+        } else if matches(NOT_FOUND) {
+            RemoteError::NotFound(message)
+        } else if matches(NETWORK) {
+            RemoteError::Network(message)
+        } else {
+            RemoteError::Clone(message)
+        }
+    }
+}
+
+/// Credentials used when cloning over HTTPS
+#[derive(Clone, Default)]
+pub struct RemoteCredentials {
+    pub username: Option<String>,
+    pub token: Option<String>,
+}
+
+impl RemoteCredentials {
+    pub fn token(token: Option<String>) -> Self {
+        Self {
+            username: None,
+            token,
+        }
+    }
+
+    /// Configure a git command to authenticate non-interactively.
+    ///
+    /// The token is passed through git's environment configuration rather
+    /// than the clone URL so it never appears in the process list or the
+    /// cloned repository's config.
+    fn apply(&self, command: &mut Command) {
+        // Never block waiting for a username/password or host key prompt
+        command.env("GIT_TERMINAL_PROMPT", "0");
+        if std::env::var_os("GIT_SSH_COMMAND").is_none() {
+            command.env("GIT_SSH_COMMAND", "ssh -o BatchMode=yes");
+        }
+        if let Some(token) = &self.token {
+            let username = self.username.as_deref().unwrap_or(DEFAULT_TOKEN_USERNAME);
+            let credentials =
+                base64::engine::general_purpose::STANDARD.encode(format!("{}:{}", username, token));
+            command
+                .env("GIT_CONFIG_COUNT", "1")
+                .env("GIT_CONFIG_KEY_0", "http.extraHeader")
+                .env(
+                    "GIT_CONFIG_VALUE_0",
+                    format!("Authorization: Basic {}", credentials),
+                );
+        }
+    }
+
+    /// Pick credentials for libgit2, which asks again after each rejection.
+    ///
+    /// Over HTTPS the token is offered once; over SSH the agent is tried
+    /// before the default key files. Nothing ever prompts interactively.
+    fn credential(
+        &self,
+        attempts: &mut CredentialAttempts,
+        username_from_url: Option<&str>,
+        allowed: CredentialType,
+    ) -> Result<Cred, git2::Error> {
+        let username = username_from_url
+            .or(self.username.as_deref())
+            .unwrap_or("git");
+
+        if allowed.contains(CredentialType::USER_PASS_PLAINTEXT) && !attempts.token {
+            if let Some(token) = &self.token {
+                attempts.token = true;
+                let username = self.username.as_deref().unwrap_or(DEFAULT_TOKEN_USERNAME);
+                return Cred::userpass_plaintext(username, token);
+            }
+        }
+        if allowed.contains(CredentialType::USERNAME) {
+            return Cred::username(username);
+        }
+        if allowed.contains(CredentialType::SSH_KEY) {
+            if !attempts.ssh_agent && std::env::var_os("SSH_AUTH_SOCK").is_some() {
+                attempts.ssh_agent = true;
+                return Cred::ssh_key_from_agent(username);
+            }
+            if let Some(key) = attempts.next_ssh_key() {
+                return Cred::ssh_key(username, None, &key, None);
+            }
+        }
+        if allowed.contains(CredentialType::DEFAULT) && !attempts.default {
+            attempts.default = true;
+            return Cred::default();
+        }
+
+        Err(git2::Error::new(
+            ErrorCode::Auth,
+            ErrorClass::Callback,
+            "authentication failed: no accepted credentials available",
+        ))
+    }
+}
+
+/// Credentials already offered during one connection
+#[derive(Default)]
+struct CredentialAttempts {
+    token: bool,
+    ssh_agent: bool,
+    ssh_keys: usize,
+    default: bool,
+}
+
+impl CredentialAttempts {
+    /// Next existing private key in `~/.ssh`
+    fn next_ssh_key(&mut self) -> Option<PathBuf> {
+        let ssh_dir = PathBuf::from(std::env::var_os("HOME")?).join(".ssh");
+        while self.ssh_keys < DEFAULT_SSH_KEYS.len() {
+            let key = ssh_dir.join(DEFAULT_SSH_KEYS[self.ssh_keys]);
+            self.ssh_keys += 1;
+            if key.exists() {
+                return Some(key);
+            }
+        }
+        None
+    }
+}
+
+/// Check that a repository URL uses a supported scheme.
+///
+/// Besides `scheme://` URLs, the scp-like `user@host:path` form is accepted
+/// as SSH.
+pub fn validate_url(url: &str) -> Result<(), RemoteError> {
+    if url.is_empty() || url.starts_with('-') {
+        return Err(RemoteError::InvalidUrl(url.to_string()));
+    }
+
+    if !url.contains("://") {
+        // scp-like syntax: [user@]host:path
+        return match url.split_once(':') {
+            Some((host, path)) if !host.is_empty() && !path.is_empty() && !host.contains('/') => {
+                Ok(())
+            }
+            _ => Err(RemoteError::InvalidUrl(format!(
+                "{} (expected an https, ssh, git or file URL)",
+                url
+            ))),
+        };
+    }
+
+    let parsed = Url::parse(url).map_err(|e| RemoteError::InvalidUrl(format!("{}: {}", url, e)))?;
+    if !SUPPORTED_SCHEMES.contains(&parsed.scheme()) {
+        return Err(RemoteError::InvalidUrl(format!(
+            "{} (unsupported scheme {})",
+            url,
+            parsed.scheme()
+        )));
+    }
+    Ok(())
+}
+
+/// Remove any password embedded in a URL so it is safe to store and display
+pub fn redact_url(url: &str) -> String {
+    match Url::parse(url) {
+        Ok(mut parsed) if parsed.password().is_some() => {
+            let _ = parsed.set_password(None);
+            parsed.to_string()
+        }
+        _ => url.to_string(),
+    }
+}
+
+/// Derive a display name such as `group/project` from a repository URL
+pub fn repo_name_from_url(url: &str) -> String {
+    let path = match Url::parse(url) {
+        Ok(parsed) => parsed.path().to_string(),
+        Err(_) => url
+            .split_once(':')
+            .map(|(_, path)| path.to_string())
+            .unwrap_or_else(|| url.to_string()),
+    };
+    let segments: Vec<&str> = path
+        .trim_end_matches('/')
+        .trim_end_matches(".git")
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .collect();
+    match segments.as_slice() {
+        [] => url.to_string(),
+        [name] => name.to_string(),
+        [.., owner, name] => format!("{}/{}", owner, name),
+    }
+}
+
+/// Clone or refresh a remote repository in the clone cache and analyze it
+pub async fn analyze_remote(
+    url: &str,
+    name: &str,
+    credentials: &RemoteCredentials,
+    strategy: CloneStrategy,
+    cache: &CloneCache,
+    executor: &AnalysisExecutor,
+    cancel: &CancellationToken,
+) -> Result<RepoAnalysis, RemoteError> {
+    // The checkout keeps the clone locked until the analysis is done
+    let checkout = checkout_repository(url, credentials, strategy, cache, cancel).await?;
+
+    let path = checkout.path().to_path_buf();
+    let job_cancel = cancel.clone();
+    let mut analysis = executor
+        .run(move || analyze_repository(&path, false, &job_cancel))
+        .await
+        .map_err(GitError::from)??;
+
+    analysis.name = name.to_string();
+    analysis.path = redact_url(url);
+    analysis.clone_strategy = Some(strategy);
+    // Without the trees of earlier commits every directory dates from HEAD
+    analysis.approximate_directory_ages = strategy == CloneStrategy::Treeless;
+
+    Ok(analysis)
+}
+
+/// Bring the cached clone of a repository up to date with the remote.
+///
+/// A cached clone is always fetched with the caller's credentials before it
+/// is used, so access to a private repository is checked on every request.
+pub async fn checkout_repository(
+    url: &str,
+    credentials: &RemoteCredentials,
+    strategy: CloneStrategy,
+    cache: &CloneCache,
+    cancel: &CancellationToken,
+) -> Result<CacheCheckout, RemoteError> {
+    let mut checkout = cache.checkout(&redact_url(url), strategy).await?;
+
+    // libgit2 cannot fetch with a filter, so partial clones use the git CLI
+    if let Some(filter) = strategy.filter() {
+        if checkout.is_populated() {
+            fetch_partial_clone(checkout.path(), credentials, cancel).await?;
+        } else {
+            partial_clone(url, checkout.path(), filter, credentials, cancel).await?;
+        }
+        checkout.finish().await?;
+        return Ok(checkout);
+    }
+
+    // The transfer blocks, so it runs on a blocking thread that owns the
+    // checkout: the entry stays locked until git2 is done with it even if
+    // this future is dropped
+    let url = url.to_string();
+    let credentials = credentials.clone();
+    let cancel = cancel.clone();
+    let (result, mut checkout) = tokio::task::spawn_blocking(move || {
+        let result = sync_repository(
+            &url,
+            checkout.path(),
+            checkout.is_populated(),
+            &credentials,
+            &cancel,
+        )
+        .map_err(|e| RemoteError::from_git(e, &cancel));
+        (result, checkout)
+    })
+    .await
+    .map_err(std::io::Error::other)?;
+    result?;
+
+    checkout.finish().await?;
+    Ok(checkout)
+}
+
+/// Fetch the remote's default branch with its full history and check it out,
+/// initializing the repository on first use
+fn sync_repository(
+    url: &str,
+    path: &Path,
+    populated: bool,
+    credentials: &RemoteCredentials,
+    cancel: &CancellationToken,
+) -> Result<(), git2::Error> {
+    let display_url = redact_url(url);
+    let repo = if populated {
+        tracing::debug!("Refreshing cached clone of {}", display_url);
+        Repository::open(path)?
+    } else {
+        tracing::debug!("Cloning {}", display_url);
+        let repo = Repository::init(path)?;
+        // Keep any password in the URL out of the cached repository's config
+        repo.remote("origin", &display_url)?;
+        repo
+    };
+    let mut remote = repo.remote_anonymous(url)?;
+
+    let default_branch = {
+        let callbacks = remote_callbacks(credentials, cancel, &display_url);
+        let connection = remote.connect_auth(Direction::Fetch, Some(callbacks), None)?;
+        if connection.list()?.is_empty() {
+            None
+        } else {
+            Some(String::from_utf8_lossy(&connection.default_branch()?).into_owned())
+        }
+    };
+    let Some(branch) = default_branch else {
+        // An empty remote leaves an unborn repository, analyzed as empty
+        tracing::info!("{} has no branches", display_url);
+        return Ok(());
+    };
+    let tracking = format!(
+        "refs/remotes/origin/{}",
+        branch.strip_prefix("refs/heads/").unwrap_or(&branch)
+    );
+
+    let mut fetch_options = FetchOptions::new();
+    fetch_options
+        .remote_callbacks(remote_callbacks(credentials, cancel, &display_url))
+        .download_tags(AutotagOption::None);
+    remote.fetch(
+        &[format!("+{}:{}", branch, tracking)],
+        Some(&mut fetch_options),
+        None,
+    )?;
+    let stats = remote.stats();
+    tracing::info!(
+        "Fetched {} objects ({} KiB) from {}",
+        stats.received_objects(),
+        stats.received_bytes() / 1024,
+        display_url
+    );
+
+    let target = repo.refname_to_id(&tracking)?;
+    repo.reference(&branch, target, true, "codecity: update from remote")?;
+    repo.set_head(&branch)?;
+    repo.checkout_head(Some(CheckoutBuilder::new().force().remove_untracked(true)))?;
+    Ok(())
+}
+
+/// Callbacks supplying credentials, logging progress and aborting the
+/// transfer once the analysis is cancelled
+fn remote_callbacks<'a>(
+    credentials: &'a RemoteCredentials,
+    cancel: &'a CancellationToken,
+    display_url: &'a str,
+) -> RemoteCallbacks<'a> {
+    let mut callbacks = RemoteCallbacks::new();
+
+    let mut attempts = CredentialAttempts::default();
+    callbacks.credentials(move |_url, username_from_url, allowed| {
+        credentials.credential(&mut attempts, username_from_url, allowed)
+    });
+
+    let mut reported_quarter = 0;
+    callbacks.transfer_progress(move |progress| {
+        let total = progress.total_objects();
+        let quarter = (progress.received_objects() * 4)
+            .checked_div(total)
+            .unwrap_or(0);
+        if quarter > reported_quarter {
+            reported_quarter = quarter;
+            tracing::debug!(
+                "{}: received {}/{} objects ({} KiB)",
+                display_url,
+                progress.received_objects(),
+                total,
+                progress.received_bytes() / 1024
+            );
+        }
+        !cancel.is_cancelled()
+    });
+
+    callbacks
+}
+
+/// Clone the default branch without the objects excluded by `filter`.
+///
+/// Blobs needed for the checkout at HEAD are still downloaded; everything
+/// else is left on the remote, which must allow filtering (on a plain git
+/// server, `uploadpack.allowFilter`).
+async fn partial_clone(
+    url: &str,
+    path: &Path,
+    filter: &str,
+    credentials: &RemoteCredentials,
+    cancel: &CancellationToken,
+) -> Result<(), RemoteError> {
+    let display_url = redact_url(url);
+    tracing::debug!("Cloning {} with filter {}", display_url, filter);
+
+    let mut clone = Command::new("git");
+    clone
+        .args(["clone", "--single-branch", "--no-tags"])
+        .arg(format!("--filter={}", filter))
+        .args(["--", url])
+        .arg(path);
+    credentials.apply(&mut clone);
+    check_output(run_git(&mut clone, cancel).await?)?;
+
+    // Keep any password in the URL out of the cached repository's config;
+    // later refreshes authenticate with the token instead
+    if display_url != url {
+        let mut set_url = Command::new("git");
+        set_url
+            .args(["remote", "set-url", "origin", &display_url])
+            .current_dir(path);
+        check_output(run_git(&mut set_url, cancel).await?)?;
+    }
+    Ok(())
+}
+
+/// Fetch new commits into a partial clone and reset the working tree to them
+async fn fetch_partial_clone(
+    path: &Path,
+    credentials: &RemoteCredentials,
+    cancel: &CancellationToken,
+) -> Result<(), RemoteError> {
+    tracing::debug!("Refreshing cached partial clone {}", path.display());
+
+    // Fetching from origin reuses the filter recorded at clone time
+    let mut fetch = Command::new("git");
+    fetch
+        .args(["fetch", "--force", "--no-tags", "origin"])
+        .current_dir(path);
+    credentials.apply(&mut fetch);
+    check_output(run_git(&mut fetch, cancel).await?)?;
+
+    // Resetting downloads the blobs of the new HEAD from the promisor remote
+    let mut reset = Command::new("git");
+    reset
+        .args(["reset", "--hard", "--quiet", "FETCH_HEAD"])
+        .current_dir(path);
+    credentials.apply(&mut reset);
+    check_output(run_git(&mut reset, cancel).await?)?;
+
+    let mut clean = Command::new("git");
+    clean.args(["clean", "-ffdxq"]).current_dir(path);
+    check_output(run_git(&mut clean, cancel).await?)?;
+    Ok(())
+}
+
+fn check_output(output: Output) -> Result<(), RemoteError> {
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(RemoteError::from_message(&String::from_utf8_lossy(
+            &output.stderr,
+        )))
+    }
+}
+
+/// Run a git command, killing it if the analysis is cancelled
+async fn run_git(command: &mut Command, cancel: &CancellationToken) -> Result<Output, RemoteError> {
+    let child = command
+        .kill_on_drop(true)
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()?;
+    tokio::select! {
+        output = child.wait_with_output() => Ok(output?),
+        _ = cancel.cancelled() => Err(RemoteError::Cancelled),
+    }
+}
+
+/// Let libgit2 open partial clones made by the git CLI.
+///
+/// libgit2 refuses repositories using extensions it does not know. It cannot
+/// fetch missing objects itself, but analysis only reads the working tree,
+/// commits and (for blobless clones) trees, which are all present locally.
+pub fn enable_partial_clone_support() {
+    static ENABLE: Once = Once::new();
+    ENABLE.call_once(|| {
+        // SAFETY: the Once runs this a single time, before any caller goes on
+        // to use libgit2. The server calls it at startup and tests call it
+        // before touching repositories, so no other thread uses libgit2 while
+        // the extensions change.
+        if let Err(e) = unsafe { git2::opts::set_extensions(&["partialclone"]) } {
+            tracing::warn!("Failed to enable partial clone support in libgit2: {}", e);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{commit_file, git, init_libgit2, init_repo, test_config};
+
+    /// Bare repository allowing partial clones, with `old/` committed 400 days
+    /// ago and `new/` 10 days ago, plus a working copy pushing to it
+    fn origin(dir: &Path) -> (String, PathBuf) {
+        let work = dir.join("work");
+        let bare = dir.join("origin.git");
+        init_repo(&work);
+        commit_file(&work, "old/lib.rs", "fn a() {}\nfn b() {}\n", 400);
+        commit_file(&work, "new/main.rs", "fn main() {}\n", 10);
+        git(
+            dir,
+            0,
+            &["clone", "--quiet", "--bare", "work", "origin.git"],
+        );
+        git(&bare, 0, &["config", "uploadpack.allowFilter", "true"]);
+        git(&work, 0, &["remote", "add", "origin", "../origin.git"]);
+        (format!("file://{}", bare.display()), work)
+    }
+
+    fn directory_age(analysis: &RepoAnalysis, name: &str) -> u64 {
+        analysis
+            .directories
+            .iter()
+            .find(|d| d.name == name)
+            .unwrap_or_else(|| panic!("no directory {}", name))
+            .age_days
+    }
+
+    #[tokio::test]
+    async fn every_clone_strategy_analyzes_and_refreshes_a_file_url() {
+        init_libgit2();
+        let dir = tempfile::tempdir().unwrap();
+        let (url, work) = origin(dir.path());
+        let config = test_config(dir.path().join("cache"));
+        let cache = CloneCache::new(&config);
+        let executor = AnalysisExecutor::new(&config);
+        let cancel = CancellationToken::new();
+        let credentials = RemoteCredentials::default();
+        let strategies = [
+            CloneStrategy::Full,
+            CloneStrategy::Blobless,
+            CloneStrategy::Treeless,
+        ];
+
+        for strategy in strategies {
+            let analysis = analyze_remote(
+                &url,
+                "origin",
+                &credentials,
+                strategy,
+                &cache,
+                &executor,
+                &cancel,
+            )
+            .await
+            .unwrap_or_else(|e| panic!("{:?} clone failed: {}", strategy, e));
+
+            assert_eq!(analysis.total_lines, 3, "{:?}", strategy);
+            assert_eq!(analysis.age_days, 400, "{:?}", strategy);
+            assert_eq!(directory_age(&analysis, "new"), 10, "{:?}", strategy);
+            if strategy == CloneStrategy::Treeless {
+                assert!(analysis.approximate_directory_ages);
+                assert_eq!(directory_age(&analysis, "old"), 10);
+            } else {
+                assert!(!analysis.approximate_directory_ages, "{:?}", strategy);
+                assert_eq!(directory_age(&analysis, "old"), 400, "{:?}", strategy);
+            }
+        }
+
+        // Cached clones of every strategy pick up new commits
+        commit_file(&work, "new/extra.rs", "fn extra() {}\n", 1);
+        git(&work, 0, &["push", "--quiet", "origin", "main"]);
+        for strategy in strategies {
+            let analysis = analyze_remote(
+                &url,
+                "origin",
+                &credentials,
+                strategy,
+                &cache,
+                &executor,
+                &cancel,
+            )
+            .await
+            .unwrap_or_else(|e| panic!("{:?} refresh failed: {}", strategy, e));
+
+            assert_eq!(analysis.total_lines, 4, "{:?}", strategy);
+        }
+        assert_eq!(cache.status().entries.len(), strategies.len());
+    }
+}
@@ -0,0 +1,365 @@
+use chrono::{DateTime, Datelike, Days, Duration, NaiveDate, Timelike, Utc};
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+#[error("Invalid schedule {expression:?}: {reason}")]
+pub struct ScheduleError {
+    expression: String,
+    reason: String,
+}
+
+const MONTH_NAMES: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const DAY_NAMES: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// A cron schedule of five fields: minute, hour, day of month, month and day
+/// of week, evaluated in UTC.
+///
+/// Fields take `*`, values, ranges `a-b`, steps `*/n`, `a/n` or `a-b/n`, and
+/// lists of these; months and days of week also take three-letter names. As
+/// in cron, when both day fields are restricted a day matching either one is
+/// a match.
+/// `@hourly`, `@daily`, `@weekly`, `@monthly` and `@yearly` are shorthands.
+#[derive(Debug, Clone)]
+pub struct Schedule {
+    expression: String,
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    day_of_month_restricted: bool,
+    day_of_week_restricted: bool,
+}
+
+impl FromStr for Schedule {
+    type Err = ScheduleError;
+
+    fn from_str(expression: &str) -> Result<Self, Self::Err> {
+        let error = |reason: String| ScheduleError {
+            expression: expression.to_string(),
+            reason,
+        };
+        let expanded = match expression.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            other => other,
+        };
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        let [minute, hour, day_of_month, month, day_of_week] = fields[..] else {
+            return Err(error(format!("expected 5 fields, got {}", fields.len())));
+        };
+
+        let mut days_of_week = parse_field(day_of_week, 0, 7, &DAY_NAMES).map_err(error)?;
+        // Both 0 and 7 are Sunday
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week = (days_of_week | 1) & !(1 << 7);
+        }
+
+        Ok(Self {
+            expression: expression.trim().to_string(),
+            minutes: parse_field(minute, 0, 59, &[]).map_err(error)?,
+            hours: parse_field(hour, 0, 23, &[]).map_err(error)?,
+            days_of_month: parse_field(day_of_month, 1, 31, &[]).map_err(error)?,
+            months: parse_field(month, 1, 12, &MONTH_NAMES).map_err(error)?,
+            days_of_week,
+            day_of_month_restricted: !day_of_month.starts_with('*'),
+            day_of_week_restricted: !day_of_week.starts_with('*'),
+        })
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.expression)
+    }
+}
+
+impl Schedule {
+    /// First time after `after`, at minute resolution, that the schedule fires
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut time = after.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        // Long enough for schedules that only fire on February 29
+        let limit = time + Duration::days(8 * 366);
+        while time < limit {
+            if !has_bit(self.months, time.month()) {
+                let (year, month) = match time.month() {
+                    12 => (time.year() + 1, 1),
+                    month => (time.year(), month + 1),
+                };
+                time = NaiveDate::from_ymd_opt(year, month, 1)?
+                    .and_hms_opt(0, 0, 0)?
+                    .and_utc();
+            } else if !self.day_matches(time) {
+                time = (time.date_naive() + Days::new(1))
+                    .and_hms_opt(0, 0, 0)?
+                    .and_utc();
+            } else if !has_bit(self.hours, time.hour()) {
+                time = time.with_minute(0)? + Duration::hours(1);
+            } else if !has_bit(self.minutes, time.minute()) {
+                time += Duration::minutes(1);
+            } else {
+                return Some(time);
+            }
+        }
+        None
+    }
+
+    fn day_matches(&self, time: DateTime<Utc>) -> bool {
+        let day_of_month = has_bit(self.days_of_month, time.day());
+        let day_of_week = has_bit(self.days_of_week, time.weekday().num_days_from_sunday());
+        match (self.day_of_month_restricted, self.day_of_week_restricted) {
+            (true, true) => day_of_month || day_of_week,
+            _ => day_of_month && day_of_week,
+        }
+    }
+}
+
+fn has_bit(bits: u64, value: u32) -> bool {
+    bits & (1 << value) != 0
+}
+
+/// Parse one cron field into a bit set of the values it matches
+fn parse_field(field: &str, min: u32, max: u32, names: &[&str]) -> Result<u64, String> {
+    let value = |text: &str| -> Result<u32, String> {
+        let lower = text.to_ascii_lowercase();
+        let parsed = match names.iter().position(|name| *name == lower) {
+            // Names count from the field's minimum: jan is 1, sun is 0
+            Some(index) => index as u32 + min,
+            None => text
+                .parse()
+                .map_err(|_| format!("invalid value {:?}", text))?,
+        };
+        if parsed < min || parsed > max {
+            return Err(format!("{} is outside {}-{}", parsed, min, max));
+        }
+        Ok(parsed)
+    };
+
+    let mut bits = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step
+                    .parse()
+                    .ok()
+                    .filter(|step| *step > 0)
+                    .ok_or_else(|| format!("invalid step {:?}", step))?;
+                (range, Some(step))
+            }
+            None => (part, None),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((start, end)) => (value(start)?, value(end)?),
+                // `a/n` runs from `a` to the end of the field, even when n is 1
+                None if step.is_some() => (value(range)?, max),
+                None => {
+                    let single = value(range)?;
+                    (single, single)
+                }
+            },
+        };
+        if start > end {
+            return Err(format!("range {:?} is backwards", range));
+        }
+        for v in (start..=end).step_by(step.unwrap_or(1) as usize) {
+            bits |= 1 << v;
+        }
+    }
+    Ok(bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(year, month, day, hour, minute, 0)
+            .unwrap()
+    }
+
+    fn next(expression: &str, after: DateTime<Utc>) -> DateTime<Utc> {
+        expression
+            .parse::<Schedule>()
+            .unwrap()
+            .next_after(after)
+            .unwrap()
+    }
+
+    #[test]
+    fn fires_on_the_next_minute_of_a_range() {
+        assert_eq!(
+            next("0 9-17 * * *", at(2024, 1, 1, 8, 59)),
+            at(2024, 1, 1, 9, 0)
+        );
+        assert_eq!(
+            next("0 9-17 * * *", at(2024, 1, 1, 9, 0)),
+            at(2024, 1, 1, 10, 0)
+        );
+        assert_eq!(
+            next("0 9-17 * * *", at(2024, 1, 1, 17, 30)),
+            at(2024, 1, 2, 9, 0)
+        );
+        assert_eq!(
+            next("15,45 * * * *", at(2024, 1, 1, 10, 20)),
+            at(2024, 1, 1, 10, 45)
+        );
+    }
+
+    #[test]
+    fn steps_through_ranges_and_from_a_start_value() {
+        assert_eq!(
+            next("*/15 * * * *", at(2024, 1, 1, 10, 7)),
+            at(2024, 1, 1, 10, 15)
+        );
+        assert_eq!(
+            next("*/15 * * * *", at(2024, 1, 1, 10, 50)),
+            at(2024, 1, 1, 11, 0)
+        );
+        assert_eq!(
+            next("10-30/10 * * * *", at(2024, 1, 1, 10, 20)),
+            at(2024, 1, 1, 10, 30)
+        );
+        assert_eq!(
+            next("10-30/10 * * * *", at(2024, 1, 1, 10, 31)),
+            at(2024, 1, 1, 11, 10)
+        );
+        assert_eq!(
+            next("50/5 * * * *", at(2024, 1, 1, 10, 52)),
+            at(2024, 1, 1, 10, 55)
+        );
+        assert_eq!(
+            next("50/5 * * * *", at(2024, 1, 1, 10, 56)),
+            at(2024, 1, 1, 11, 50)
+        );
+        // `a/1` is every minute from `a`, not just `a`
+        assert_eq!(
+            next("5/1 * * * *", at(2024, 1, 1, 0, 10)),
+            at(2024, 1, 1, 0, 11)
+        );
+        assert_eq!(
+            next("5/1 * * * *", at(2024, 1, 1, 0, 59)),
+            at(2024, 1, 1, 1, 5)
+        );
+    }
+
+    #[test]
+    fn accepts_month_and_day_names() {
+        // 2024-01-06 is a Saturday
+        assert_eq!(
+            next("0 8 * * MON-FRI", at(2024, 1, 6, 12, 0)),
+            at(2024, 1, 8, 8, 0)
+        );
+        assert_eq!(
+            next("0 8 * * sat,sun", at(2024, 1, 8, 12, 0)),
+            at(2024, 1, 13, 8, 0)
+        );
+        assert_eq!(
+            next("0 0 1 jul *", at(2024, 1, 1, 0, 0)),
+            at(2024, 7, 1, 0, 0)
+        );
+        assert_eq!(
+            next("0 0 1 Nov-Dec *", at(2024, 12, 2, 0, 0)),
+            at(2025, 11, 1, 0, 0)
+        );
+    }
+
+    #[test]
+    fn treats_both_0_and_7_as_sunday() {
+        // 2024-01-01 is a Monday
+        let sunday = at(2024, 1, 7, 0, 0);
+        for expression in ["0 0 * * 0", "0 0 * * 7", "0 0 * * sun", "@weekly"] {
+            assert_eq!(
+                next(expression, at(2024, 1, 1, 0, 0)),
+                sunday,
+                "{}",
+                expression
+            );
+        }
+        assert_eq!(
+            next("0 0 * * 5-7", at(2024, 1, 7, 12, 0)),
+            at(2024, 1, 12, 0, 0)
+        );
+    }
+
+    #[test]
+    fn matches_either_day_field_when_both_are_restricted() {
+        // 2024-09-01 is a Sunday; Friday the 6th comes before the 13th
+        assert_eq!(
+            next("0 0 13 * fri", at(2024, 9, 1, 0, 0)),
+            at(2024, 9, 6, 0, 0)
+        );
+        assert_eq!(
+            next("0 0 13 * fri", at(2024, 9, 6, 0, 0)),
+            at(2024, 9, 13, 0, 0)
+        );
+        assert_eq!(
+            next("0 0 2 * fri", at(2024, 9, 1, 0, 0)),
+            at(2024, 9, 2, 0, 0)
+        );
+        // A wildcard day field leaves the other one to decide alone
+        assert_eq!(
+            next("0 0 13 * *", at(2024, 9, 1, 0, 0)),
+            at(2024, 9, 13, 0, 0)
+        );
+        assert_eq!(
+            next("0 0 * * fri", at(2024, 9, 1, 0, 0)),
+            at(2024, 9, 6, 0, 0)
+        );
+        // As in cron, a stepped wildcard still counts as a wildcard
+        assert_eq!(
+            next("0 0 */10 * fri", at(2024, 9, 1, 0, 0)),
+            at(2024, 10, 11, 0, 0)
+        );
+    }
+
+    #[test]
+    fn finds_february_29_in_the_next_leap_year() {
+        assert_eq!(
+            next("0 0 29 2 *", at(2025, 1, 1, 0, 0)),
+            at(2028, 2, 29, 0, 0)
+        );
+        assert_eq!(
+            next("0 0 29 2 *", at(2024, 2, 29, 0, 0)),
+            at(2028, 2, 29, 0, 0)
+        );
+        assert_eq!(
+            next("@monthly", at(2024, 1, 31, 12, 0)),
+            at(2024, 2, 1, 0, 0)
+        );
+    }
+
+    #[test]
+    fn never_fires_on_impossible_dates() {
+        let schedule: Schedule = "0 0 30 2 *".parse().unwrap();
+        assert_eq!(schedule.next_after(at(2024, 1, 1, 0, 0)), None);
+    }
+
+    #[test]
+    fn rejects_invalid_expressions() {
+        for expression in [
+            "* * * *",
+            "* * * * * *",
+            "60 * * * *",
+            "* 24 * * *",
+            "* * 0 * *",
+            "* * * 13 *",
+            "* * * * 8",
+            "*/0 * * * *",
+            "30-10 * * * *",
+            "* * * foo *",
+            "@often",
+        ] {
+            assert!(expression.parse::<Schedule>().is_err(), "{}", expression);
+        }
+    }
+}
@@ -0,0 +1,342 @@
+use crate::api::{keep_previous_analyses, store_directory_analysis, AppState};
+use crate::git::{analyze_directory, analyze_repository, DirectoryAnalysis, GitError, ScanOptions};
+use crate::models::{AnalyzeLocalRequest, AnalyzeRemoteRequest, ScanDirectoryRequest};
+use crate::provider::{analyze_remote_request, ProviderError};
+use crate::schedule::Schedule;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+use uuid::Uuid;
+
+/// Longest the scheduler sleeps before checking for due sources again
+const MAX_WAIT: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// What a tracked source analyzes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SourceKind {
+    /// A single local repository
+    Local(AnalyzeLocalRequest),
+    /// Every repository found below a directory
+    Scan(ScanDirectoryRequest),
+    /// A repository on GitHub or GitLab
+    Remote(AnalyzeRemoteRequest),
+}
+
+impl SourceKind {
+    fn target(&self) -> String {
+        match self {
+            SourceKind::Local(request) => request.path.clone(),
+            SourceKind::Scan(request) => request.path.clone(),
+            SourceKind::Remote(request) => format!("{}/{}", request.owner, request.repo),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AddSourceRequest {
+    #[serde(flatten)]
+    pub source: SourceKind,
+    /// Cron expression such as `0 3 * * *`, in UTC
+    pub schedule: String,
+    /// Analyze the source right away instead of waiting for the schedule
+    #[serde(default)]
+    pub run_now: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+/// Outcome of one scheduled analysis of a source
+#[derive(Debug, Clone, Serialize)]
+pub struct SourceRun {
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub status: RunStatus,
+    pub error: Option<String>,
+    /// Ids of the repositories analyzed by the run
+    pub repo_ids: Vec<String>,
+    /// Repositories below a scan root that could not be analyzed
+    pub failures: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TrackedSource {
+    pub id: String,
+    #[serde(flatten)]
+    pub source: SourceKind,
+    /// Cron expression the source is analyzed on
+    pub schedule: String,
+    pub created_at: DateTime<Utc>,
+    pub running: bool,
+    pub last_run: Option<SourceRun>,
+    pub next_run: Option<DateTime<Utc>>,
+}
+
+struct Entry {
+    info: TrackedSource,
+    schedule: Schedule,
+}
+
+/// Sources that are re-analyzed periodically by [`run_schedules`]
+#[derive(Clone, Default)]
+pub struct SourceRegistry {
+    inner: Arc<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    sources: Mutex<HashMap<String, Entry>>,
+    /// Wakes the scheduler when sources are added or removed
+    changed: Notify,
+}
+
+impl SourceRegistry {
+    /// Track a source, first analyzed now or when its schedule next fires
+    pub fn add(&self, source: SourceKind, schedule: Schedule, run_now: bool) -> TrackedSource {
+        let now = Utc::now();
+        let info = TrackedSource {
+            id: Uuid::new_v4().to_string(),
+            source,
+            schedule: schedule.to_string(),
+            created_at: now,
+            running: false,
+            last_run: None,
+            next_run: if run_now {
+                Some(now)
+            } else {
+                schedule.next_after(now)
+            },
+        };
+        self.inner.sources.lock().unwrap().insert(
+            info.id.clone(),
+            Entry {
+                info: info.clone(),
+                schedule,
+            },
+        );
+        self.inner.changed.notify_one();
+        info
+    }
+
+    /// Stop tracking a source, returning whether it was found.
+    ///
+    /// Repositories it analyzed stay stored.
+    pub fn remove(&self, id: &str) -> bool {
+        let removed = self.inner.sources.lock().unwrap().remove(id).is_some();
+        self.inner.changed.notify_one();
+        removed
+    }
+
+    pub fn list(&self) -> Vec<TrackedSource> {
+        let mut sources: Vec<TrackedSource> = self
+            .inner
+            .sources
+            .lock()
+            .unwrap()
+            .values()
+            .map(|entry| entry.info.clone())
+            .collect();
+        sources.sort_by_key(|source| source.created_at);
+        sources
+    }
+
+    /// Mark sources whose next run has come as running and return them
+    fn take_due(&self, now: DateTime<Utc>) -> Vec<TrackedSource> {
+        let mut sources = self.inner.sources.lock().unwrap();
+        sources
+            .values_mut()
+            .filter(|entry| !entry.info.running && entry.info.next_run.is_some_and(|t| t <= now))
+            .map(|entry| {
+                entry.info.running = true;
+                entry.info.next_run = entry.schedule.next_after(now);
+                entry.info.clone()
+            })
+            .collect()
+    }
+
+    fn finish(&self, id: &str, run: SourceRun) {
+        if let Some(entry) = self.inner.sources.lock().unwrap().get_mut(id) {
+            entry.info.running = false;
+            entry.info.last_run = Some(run);
+        }
+    }
+
+    fn next_wakeup(&self) -> Option<DateTime<Utc>> {
+        self.inner
+            .sources
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|entry| !entry.info.running)
+            .filter_map(|entry| entry.info.next_run)
+            .min()
+    }
+
+    /// How long the scheduler may sleep: until the next run, at most [`MAX_WAIT`]
+    fn time_until_wakeup(&self, now: DateTime<Utc>) -> std::time::Duration {
+        match self.next_wakeup() {
+            // A run already due is started right away
+            Some(next) => (next - now).to_std().unwrap_or_default().min(MAX_WAIT),
+            None => MAX_WAIT,
+        }
+    }
+}
+
+/// Analyze tracked sources as their schedules come due; runs for the life of the server
+pub async fn run_schedules(state: AppState) {
+    loop {
+        let now = Utc::now();
+        for source in state.sources.take_due(now) {
+            tokio::spawn(run_source(state.clone(), source));
+        }
+
+        let wait = state.sources.time_until_wakeup(Utc::now());
+        tokio::select! {
+            _ = tokio::time::sleep(wait) => {}
+            _ = state.sources.inner.changed.notified() => {}
+        }
+    }
+}
+
+async fn run_source(state: AppState, source: TrackedSource) {
+    let started_at = Utc::now();
+    let target = source.source.target();
+    tracing::info!("Running scheduled analysis of {}", target);
+
+    let result = match state
+        .jobs
+        .start(Some(format!("source-{}", source.id)), "source", &target)
+    {
+        Some(job) => analyze_source(&state, &source.source, job.token()).await,
+        None => Err(ProviderError::Cancelled),
+    };
+    let previous_ids = source
+        .last_run
+        .as_ref()
+        .map(|run| run.repo_ids.clone())
+        .unwrap_or_default();
+
+    let (status, error, repo_ids, failures) = match result {
+        Ok(mut analysis) => {
+            keep_previous_analyses(&state, &mut analysis.repos, &previous_ids).await;
+            let response = store_directory_analysis(&state.repos, &state.failures, analysis).await;
+            let repo_ids = response.repos.iter().map(|repo| repo.id.clone()).collect();
+            (
+                RunStatus::Succeeded,
+                None,
+                repo_ids,
+                response.failures.len(),
+            )
+        }
+        // Keep pointing at the previous analyses so the next run replaces them
+        Err(ProviderError::Cancelled) => (RunStatus::Cancelled, None, previous_ids, 0),
+        Err(e) => {
+            tracing::warn!("Scheduled analysis of {} failed: {}", target, e);
+            (RunStatus::Failed, Some(e.to_string()), previous_ids, 0)
+        }
+    };
+
+    state.sources.finish(
+        &source.id,
+        SourceRun {
+            started_at,
+            finished_at: Utc::now(),
+            status,
+            error,
+            repo_ids,
+            failures,
+        },
+    );
+    state.sources.inner.changed.notify_one();
+}
+
+async fn analyze_source(
+    state: &AppState,
+    source: &SourceKind,
+    cancel: &tokio_util::sync::CancellationToken,
+) -> Result<DirectoryAnalysis, ProviderError> {
+    let analysis = match source {
+        SourceKind::Local(request) => {
+            let path = request.path.clone();
+            let cancel = cancel.clone();
+            state
+                .executor
+                .run(move || analyze_repository(Path::new(&path), false, &cancel))
+                .await
+                .map_err(GitError::from)??
+        }
+        SourceKind::Scan(request) => {
+            let options = ScanOptions::from_request(request)?;
+            return Ok(analyze_directory(&request.path, options, &state.executor, cancel).await?);
+        }
+        SourceKind::Remote(request) => {
+            analyze_remote_request(
+                &state.providers,
+                request,
+                &state.cache,
+                &state.executor,
+                cancel,
+            )
+            .await?
+        }
+    };
+    Ok(DirectoryAnalysis {
+        repos: vec![analysis],
+        failures: Vec::new(),
+        total_found: 1,
+        truncated: false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local() -> SourceKind {
+        SourceKind::Local(AnalyzeLocalRequest {
+            path: "/src/app".to_string(),
+            job_id: None,
+        })
+    }
+
+    #[test]
+    fn waits_the_longest_without_scheduled_runs() {
+        let sources = SourceRegistry::default();
+        assert_eq!(sources.time_until_wakeup(Utc::now()), MAX_WAIT);
+    }
+
+    #[test]
+    fn wakes_at_once_for_due_runs_and_ignores_running_sources() {
+        let sources = SourceRegistry::default();
+        let daily: Schedule = "0 3 * * *".parse().unwrap();
+        sources.add(local(), daily, true);
+        let later = "2100-01-01T12:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        assert_eq!(sources.time_until_wakeup(later), std::time::Duration::ZERO);
+
+        // While it runs, its next run is hours away
+        assert_eq!(sources.take_due(later).len(), 1);
+        assert_eq!(sources.time_until_wakeup(later), MAX_WAIT);
+    }
+
+    #[test]
+    fn sleeps_until_a_run_due_within_the_longest_wait() {
+        let sources = SourceRegistry::default();
+        let minutely: Schedule = "* * * * *".parse().unwrap();
+        sources.add(local(), minutely, false);
+        let wait = sources.time_until_wakeup(Utc::now());
+        assert!(
+            wait > std::time::Duration::ZERO && wait <= MAX_WAIT,
+            "{:?}",
+            wait
+        );
+    }
+}
@@ -0,0 +1,87 @@
+use crate::git::calculate_language_breakdown;
+use crate::models::{
+    get_language_color, LanguageRepoCount, PortfolioSummary, RepoAnalysis, RepoRef, RepoStatus,
+    ScanFailure,
+};
+use std::collections::HashMap;
+
+/// Number of repositories listed in the "largest" section of a summary
+const LARGEST_REPOS: usize = 10;
+
+/// Aggregate portfolio-wide statistics across a set of analyzed repositories
+pub fn summarize(repos: &[RepoAnalysis], failures: Vec<ScanFailure>) -> PortfolioSummary {
+    let total_lines: u64 = repos.iter().map(|r| r.total_lines).sum();
+
+    // Lines by language across all repositories
+    let mut language_stats: HashMap<String, u64> = HashMap::new();
+    for repo in repos {
+        for lang in &repo.languages {
+            *language_stats.entry(lang.language.clone()).or_insert(0) += lang.lines;
+        }
+    }
+    let languages = calculate_language_breakdown(&language_stats, total_lines);
+
+    // Number of repositories per primary (largest) language
+    let mut primary_counts: HashMap<String, usize> = HashMap::new();
+    for repo in repos {
+        if let Some(primary) = repo.primary_language() {
+            *primary_counts.entry(primary.to_string()).or_insert(0) += 1;
+        }
+    }
+    let mut primary_languages: Vec<LanguageRepoCount> = primary_counts
+        .into_iter()
+        .map(|(language, repos)| LanguageRepoCount {
+            color: get_language_color(&language).to_string(),
+            language,
+            repos,
+        })
+        .collect();
+    primary_languages.sort_by(|a, b| b.repos.cmp(&a.repos).then(a.language.cmp(&b.language)));
+
+    // Empty and unborn repositories have no commits to date them by, so they
+    // would otherwise always be the newest. Ties go to the lowest id.
+    let dated = || {
+        repos
+            .iter()
+            .filter(|r| !matches!(r.status, RepoStatus::Empty | RepoStatus::Unborn))
+    };
+    let oldest = dated()
+        .max_by(|a, b| a.age_days.cmp(&b.age_days).then(b.id.cmp(&a.id)))
+        .map(repo_ref);
+    let newest = dated()
+        .min_by(|a, b| a.age_days.cmp(&b.age_days).then(a.id.cmp(&b.id)))
+        .map(repo_ref);
+
+    let mut by_size: Vec<&RepoAnalysis> = repos.iter().collect();
+    by_size.sort_by_key(|r| std::cmp::Reverse(r.total_lines));
+    let largest = by_size
+        .into_iter()
+        .take(LARGEST_REPOS)
+        .map(repo_ref)
+        .collect();
+
+    PortfolioSummary {
+        total_repos: repos.len(),
+        total_lines,
+        languages,
+        oldest,
+        newest,
+        largest,
+        primary_languages,
+        failures,
+    }
+}
+
+/// Reference to a repository under its display name
+pub fn repo_ref(repo: &RepoAnalysis) -> RepoRef {
+    RepoRef {
+        id: repo.id.clone(),
+        name: repo
+            .display_name
+            .clone()
+            .unwrap_or_else(|| repo.name.clone()),
+        path: repo.path.clone(),
+        age_days: repo.age_days,
+        total_lines: repo.total_lines,
+    }
+}
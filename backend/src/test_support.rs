@@ -0,0 +1,77 @@
+//! Helpers shared by unit tests
+
+use crate::config::Config;
+use axum::Router;
+use chrono::{Duration, Utc};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Serve `router` on an ephemeral local port, returning its base URL
+pub async fn serve(router: Router) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+    format!("http://{}", addr)
+}
+
+/// Configuration with small limits and no tokens, caching clones in `cache_dir`
+pub fn test_config(cache_dir: PathBuf) -> Config {
+    Config {
+        max_concurrent_repos: 2,
+        file_threads: 2,
+        max_queued_repos: 16,
+        github_token: None,
+        github_api_url: None,
+        gitlab_token: None,
+        gitlab_url: "https://gitlab.example".to_string(),
+        webhook_secret: None,
+        cache_dir,
+        cache_max_bytes: 1024 * 1024 * 1024,
+    }
+}
+
+/// Set libgit2's global options; call before a test touches libgit2.
+///
+/// The options are set once, and every test using libgit2 waits for that
+/// here, so no thread uses libgit2 while they change.
+pub fn init_libgit2() {
+    crate::remote::enable_partial_clone_support();
+}
+
+/// Run the git CLI in `dir` with author and committer dated `days_ago`
+pub fn git(dir: &Path, days_ago: i64, args: &[&str]) {
+    let date = format!(
+        "{} +0000",
+        (Utc::now() - Duration::days(days_ago)).timestamp()
+    );
+    let output = Command::new("git")
+        .args(["-c", "user.name=Test", "-c", "user.email=test@example.com"])
+        .args(["-c", "protocol.file.allow=always"])
+        .args(args)
+        .current_dir(dir)
+        .env("GIT_AUTHOR_DATE", &date)
+        .env("GIT_COMMITTER_DATE", &date)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "git {:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+/// Write a file below a working copy and commit everything, dated `days_ago`
+pub fn commit_file(work: &Path, file: &str, content: &str, days_ago: i64) {
+    let path = work.join(file);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(path, content).unwrap();
+    git(work, days_ago, &["add", "--all"]);
+    git(work, days_ago, &["commit", "--quiet", "-m", file]);
+}
+
+/// Initialize a repository on branch `main` in `dir`
+pub fn init_repo(dir: &Path) {
+    std::fs::create_dir_all(dir).unwrap();
+    git(dir, 0, &["init", "--quiet", "--initial-branch=main"]);
+}
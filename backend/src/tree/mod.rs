@@ -0,0 +1,279 @@
+use crate::git::{calculate_language_breakdown, sort_directories, TreeOptions};
+use crate::models::{DirectoryNode, TreeQuery};
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// Name of the node that directories folded away by `top` or `min_lines` are merged into
+const OTHER_NAME: &str = "(other)";
+
+#[derive(Error, Debug)]
+pub enum TreeError {
+    #[error("Directory not found: {0}")]
+    PathNotFound(String),
+    #[error("depth must be at least 1")]
+    InvalidDepth,
+}
+
+/// Select and trim a repository's directory tree.
+///
+/// `path` picks a single subtree; it is looked up before `compress` collapses
+/// chains, so any directory can be picked. At every level, children are
+/// ordered by size and those beyond the `top` largest or below `min_lines`
+/// are merged into one "(other)" node. Nodes at `depth` lose their children,
+/// with `child_count` telling how many there were.
+pub fn query_tree(
+    directories: &[DirectoryNode],
+    query: &TreeQuery,
+) -> Result<Vec<DirectoryNode>, TreeError> {
+    if query.depth == Some(0) {
+        return Err(TreeError::InvalidDepth);
+    }
+
+    let (nodes, parent_path) = match query.path.as_deref().map(|p| p.trim_matches('/')) {
+        Some(path) if !path.is_empty() => {
+            let node = find_node(directories, path)
+                .ok_or_else(|| TreeError::PathNotFound(path.to_string()))?;
+            let parent_path = path.rsplit_once('/').map(|(parent, _)| parent);
+            (vec![node.clone()], parent_path.unwrap_or("").to_string())
+        }
+        _ => (directories.to_vec(), String::new()),
+    };
+    let options = TreeOptions {
+        compress_chains: query.compress,
+    };
+    let nodes = options.apply(nodes);
+
+    Ok(shape_level(nodes, &parent_path, query, query.depth))
+}
+
+fn find_node<'a>(nodes: &'a [DirectoryNode], path: &str) -> Option<&'a DirectoryNode> {
+    nodes.iter().find_map(|node| {
+        if node.path == path {
+            Some(node)
+        } else {
+            find_node(&node.children, path)
+        }
+    })
+}
+
+fn shape_level(
+    mut nodes: Vec<DirectoryNode>,
+    parent_path: &str,
+    query: &TreeQuery,
+    depth: Option<usize>,
+) -> Vec<DirectoryNode> {
+    sort_directories(&mut nodes);
+
+    let mut kept = Vec::new();
+    let mut folded = Vec::new();
+    for (index, mut node) in nodes.into_iter().enumerate() {
+        let beyond_top = query.top.is_some_and(|top| index >= top);
+        let too_small = query
+            .min_lines
+            .is_some_and(|min_lines| node.total_lines < min_lines);
+        if beyond_top || too_small {
+            folded.push(node);
+            continue;
+        }
+
+        let children = std::mem::take(&mut node.children);
+        match depth {
+            Some(1) => node.child_count = Some(children.len()),
+            _ => {
+                node.children = shape_level(children, &node.path, query, depth.map(|d| d - 1));
+            }
+        }
+        kept.push(node);
+    }
+
+    if !folded.is_empty() {
+        kept.push(other_node(&folded, parent_path));
+    }
+    kept
+}
+
+/// Merge directories into a single childless node standing in for all of them
+fn other_node(folded: &[DirectoryNode], parent_path: &str) -> DirectoryNode {
+    let mut language_stats: HashMap<String, u64> = HashMap::new();
+    for language in folded.iter().flat_map(|node| &node.total_languages) {
+        *language_stats.entry(language.language.clone()).or_insert(0) += language.lines;
+    }
+    let lines = folded.iter().map(|node| node.total_lines).sum();
+    let languages = calculate_language_breakdown(&language_stats, lines);
+
+    DirectoryNode {
+        name: OTHER_NAME.to_string(),
+        path: if parent_path.is_empty() {
+            OTHER_NAME.to_string()
+        } else {
+            format!("{}/{}", parent_path, OTHER_NAME)
+        },
+        age_days: folded.iter().map(|node| node.age_days).max().unwrap_or(0),
+        lines,
+        languages: languages.clone(),
+        total_lines: lines,
+        total_languages: languages,
+        children: Vec::new(),
+        child_count: None,
+        folded: Some(folded.len()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::{build_directory_tree, DirStats, TreeOptions};
+    use tokio_util::sync::CancellationToken;
+
+    /// `src` with three subdirectories, `docs` and a tiny `scripts`
+    fn directories() -> Vec<DirectoryNode> {
+        let dirs = [
+            ("src/app", "Rust", 100, 10),
+            ("src/lib", "Rust", 50, 20),
+            ("src/util", "Python", 5, 300),
+            ("docs", "JavaScript", 30, 40),
+            ("scripts", "Python", 2, 500),
+        ];
+        let dir_stats: HashMap<String, DirStats> = dirs
+            .iter()
+            .map(|(path, language, lines, _)| {
+                let stats = DirStats {
+                    lines: *lines,
+                    languages: HashMap::from([(language.to_string(), *lines)]),
+                };
+                (path.to_string(), stats)
+            })
+            .collect();
+        let age = |path: &str| {
+            dirs.iter()
+                .find(|(p, ..)| *p == path)
+                .map_or(5, |(.., age)| *age)
+        };
+        build_directory_tree(
+            &dir_stats,
+            age,
+            TreeOptions::default(),
+            &CancellationToken::new(),
+        )
+        .unwrap()
+    }
+
+    fn query(query: TreeQuery) -> Vec<DirectoryNode> {
+        query_tree(&directories(), &query).unwrap()
+    }
+
+    fn paths(nodes: &[DirectoryNode]) -> Vec<&str> {
+        nodes.iter().map(|node| node.path.as_str()).collect()
+    }
+
+    fn languages(node: &DirectoryNode) -> Vec<(&str, u64)> {
+        node.total_languages
+            .iter()
+            .map(|l| (l.language.as_str(), l.lines))
+            .collect()
+    }
+
+    #[test]
+    fn returns_the_whole_tree_ordered_by_size() {
+        let nodes = query(TreeQuery::default());
+        assert_eq!(paths(&nodes), ["src", "docs", "scripts"]);
+        assert_eq!(
+            paths(&nodes[0].children),
+            ["src/app", "src/lib", "src/util"]
+        );
+        assert_eq!(nodes[0].child_count, None);
+    }
+
+    #[test]
+    fn picks_the_subtree_at_a_path() {
+        let nodes = query(TreeQuery {
+            path: Some("/src/lib/".to_string()),
+            ..TreeQuery::default()
+        });
+        assert_eq!(paths(&nodes), ["src/lib"]);
+        assert_eq!(nodes[0].total_lines, 50);
+
+        let missing = query_tree(
+            &directories(),
+            &TreeQuery {
+                path: Some("src/missing".to_string()),
+                ..TreeQuery::default()
+            },
+        );
+        assert!(matches!(missing, Err(TreeError::PathNotFound(path)) if path == "src/missing"));
+    }
+
+    #[test]
+    fn depth_leaves_out_deeper_children_and_counts_them() {
+        let nodes = query(TreeQuery {
+            depth: Some(1),
+            ..TreeQuery::default()
+        });
+        assert!(nodes.iter().all(|node| node.children.is_empty()));
+        assert_eq!(nodes[0].child_count, Some(3));
+        assert_eq!(nodes[1].child_count, Some(0));
+
+        let nodes = query(TreeQuery {
+            depth: Some(2),
+            ..TreeQuery::default()
+        });
+        assert_eq!(nodes[0].child_count, None);
+        assert_eq!(
+            paths(&nodes[0].children),
+            ["src/app", "src/lib", "src/util"]
+        );
+        assert_eq!(nodes[0].children[0].child_count, Some(0));
+    }
+
+    #[test]
+    fn rejects_depth_zero() {
+        let result = query_tree(
+            &directories(),
+            &TreeQuery {
+                depth: Some(0),
+                ..TreeQuery::default()
+            },
+        );
+        assert!(matches!(result, Err(TreeError::InvalidDepth)));
+    }
+
+    #[test]
+    fn top_folds_smaller_directories_into_other() {
+        let nodes = query(TreeQuery {
+            top: Some(1),
+            ..TreeQuery::default()
+        });
+        assert_eq!(paths(&nodes), ["src", "(other)"]);
+
+        let other = &nodes[1];
+        assert_eq!(other.name, "(other)");
+        assert_eq!(other.folded, Some(2));
+        assert_eq!(other.total_lines, 32);
+        assert_eq!(other.age_days, 500);
+        assert_eq!(languages(other), [("JavaScript", 30), ("Python", 2)]);
+        assert!(other.children.is_empty());
+
+        let src = &nodes[0];
+        assert_eq!(paths(&src.children), ["src/app", "src/(other)"]);
+        let other = &src.children[1];
+        assert_eq!(other.folded, Some(2));
+        assert_eq!(other.total_lines, 55);
+        assert_eq!(other.age_days, 300);
+        assert_eq!(languages(other), [("Rust", 50), ("Python", 5)]);
+    }
+
+    #[test]
+    fn min_lines_folds_small_directories_into_other() {
+        let nodes = query(TreeQuery {
+            min_lines: Some(10),
+            ..TreeQuery::default()
+        });
+        assert_eq!(paths(&nodes), ["src", "docs", "(other)"]);
+        assert_eq!(nodes[2].folded, Some(1));
+        assert_eq!(nodes[2].age_days, 500);
+        assert_eq!(
+            paths(&nodes[0].children),
+            ["src/app", "src/lib", "src/(other)"]
+        );
+    }
+}
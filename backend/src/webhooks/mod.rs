@@ -0,0 +1,321 @@
+use crate::config::Config;
+use crate::models::RepoAnalysis;
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+use thiserror::Error;
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[derive(Error, Debug)]
+pub enum WebhookError {
+    #[error("Webhooks are disabled; set CODECITY_WEBHOOK_SECRET to enable them")]
+    Disabled,
+    #[error("Missing signature header")]
+    MissingSignature,
+    #[error("Invalid signature")]
+    InvalidSignature,
+    #[error("Invalid payload: {0}")]
+    Payload(#[from] serde_json::Error),
+}
+
+/// Secret shared with the services that send push webhooks
+#[derive(Clone)]
+pub struct WebhookConfig {
+    pub secret: Option<String>,
+}
+
+impl WebhookConfig {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            secret: config.webhook_secret.clone(),
+        }
+    }
+
+    /// Check a `sha256=<hex>` HMAC signature of the request body, as sent by
+    /// GitHub in `X-Hub-Signature-256`
+    pub fn verify(&self, signature: Option<&str>, body: &[u8]) -> Result<(), WebhookError> {
+        let secret = self.secret.as_ref().ok_or(WebhookError::Disabled)?;
+        let signature = signature.ok_or(WebhookError::MissingSignature)?;
+        let expected = signature
+            .strip_prefix("sha256=")
+            .and_then(|digest| hex::decode(digest).ok())
+            .ok_or(WebhookError::InvalidSignature)?;
+
+        let mut mac =
+            HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+        mac.update(body);
+        // Constant-time comparison
+        mac.verify_slice(&expected)
+            .map_err(|_| WebhookError::InvalidSignature)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct GithubPushEvent {
+    #[serde(rename = "ref")]
+    git_ref: String,
+    #[serde(default)]
+    deleted: bool,
+    repository: GithubPushRepository,
+}
+
+#[derive(Debug, Deserialize)]
+struct GithubPushRepository {
+    clone_url: Option<String>,
+    ssh_url: Option<String>,
+    git_url: Option<String>,
+    html_url: Option<String>,
+    default_branch: Option<String>,
+}
+
+/// Body of the generic push webhook
+#[derive(Debug, Deserialize)]
+struct GenericPushEvent {
+    /// Clone URL, web URL or local path of the pushed repository
+    url: String,
+}
+
+/// URLs identifying the repository of a GitHub push.
+///
+/// Analyses follow the default branch, so other events, pushes to other
+/// branches and branch deletions return `None`.
+pub fn github_push_urls(
+    event: Option<&str>,
+    body: &[u8],
+) -> Result<Option<Vec<String>>, WebhookError> {
+    if event != Some("push") {
+        return Ok(None);
+    }
+    let push: GithubPushEvent = serde_json::from_slice(body)?;
+    let repository = push.repository;
+    let default_ref = repository
+        .default_branch
+        .as_ref()
+        .map(|branch| format!("refs/heads/{}", branch));
+    if push.deleted || default_ref.is_some_and(|default_ref| default_ref != push.git_ref) {
+        return Ok(None);
+    }
+
+    Ok(Some(
+        [
+            repository.clone_url,
+            repository.ssh_url,
+            repository.git_url,
+            repository.html_url,
+        ]
+        .into_iter()
+        .flatten()
+        .collect(),
+    ))
+}
+
+/// URL identifying the repository of a generic push
+pub fn generic_push_urls(body: &[u8]) -> Result<Vec<String>, WebhookError> {
+    let push: GenericPushEvent = serde_json::from_slice(body)?;
+    Ok(vec![push.url])
+}
+
+/// Whether a stored analysis is of the repository identified by `urls`
+pub fn matches_push(analysis: &RepoAnalysis, urls: &[String]) -> bool {
+    let web_url = analysis
+        .remote
+        .as_ref()
+        .and_then(|remote| remote.html_url.as_deref());
+    urls.iter().map(|url| normalize_url(url)).any(|url| {
+        url == normalize_url(&analysis.path) || web_url.is_some_and(|web| url == normalize_url(web))
+    })
+}
+
+/// Compare URLs regardless of case, trailing slashes and a `.git` suffix
+fn normalize_url(url: &str) -> String {
+    let url = url.trim().trim_end_matches('/');
+    url.strip_suffix(".git").unwrap_or(url).to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{RemoteMetadata, RepoStatus};
+    use serde_json::json;
+
+    const SECRET: &str = "It's a Secret to Everybody";
+
+    fn webhooks() -> WebhookConfig {
+        WebhookConfig {
+            secret: Some(SECRET.to_string()),
+        }
+    }
+
+    fn sign(body: &[u8]) -> String {
+        let mut mac = HmacSha256::new_from_slice(SECRET.as_bytes()).unwrap();
+        mac.update(body);
+        format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+    }
+
+    fn push(git_ref: &str, deleted: bool) -> Vec<u8> {
+        serde_json::to_vec(&json!({
+            "ref": git_ref,
+            "deleted": deleted,
+            "repository": {
+                "clone_url": "https://github.com/Octo/City.git",
+                "ssh_url": "git@github.com:Octo/City.git",
+                "html_url": "https://github.com/Octo/City",
+                "default_branch": "main",
+            },
+        }))
+        .unwrap()
+    }
+
+    fn analysis(path: &str, html_url: Option<&str>) -> RepoAnalysis {
+        RepoAnalysis {
+            id: "id".to_string(),
+            name: "octo/city".to_string(),
+            display_name: None,
+            path: path.to_string(),
+            age_days: 0,
+            total_lines: 0,
+            languages: Vec::new(),
+            directories: Vec::new(),
+            status: RepoStatus::Ok,
+            parent_id: None,
+            remote: html_url.map(|url| RemoteMetadata {
+                html_url: Some(url.to_string()),
+                ..RemoteMetadata::default()
+            }),
+            estimated: false,
+            clone_strategy: None,
+            approximate_directory_ages: false,
+            truncated: false,
+        }
+    }
+
+    #[test]
+    fn accepts_a_valid_signature() {
+        let body = b"Hello, World!";
+        // Example from GitHub's webhook documentation
+        let signature = "sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17";
+
+        assert_eq!(sign(body), signature);
+        assert!(webhooks().verify(Some(signature), body).is_ok());
+    }
+
+    #[test]
+    fn rejects_missing_and_wrong_signatures() {
+        let body = b"Hello, World!";
+        let signature = sign(body);
+
+        assert!(matches!(
+            webhooks().verify(None, body),
+            Err(WebhookError::MissingSignature)
+        ));
+        for wrong in [
+            sign(b"Hello, World?"),
+            signature.replace("sha256=", "sha1="),
+            signature.replace("sha256=", ""),
+            "sha256=not-hex".to_string(),
+            "sha256=".to_string(),
+        ] {
+            assert!(
+                matches!(
+                    webhooks().verify(Some(&wrong), body),
+                    Err(WebhookError::InvalidSignature)
+                ),
+                "{} was accepted",
+                wrong
+            );
+        }
+        assert!(matches!(
+            webhooks().verify(Some(&signature), b"Hello, World?"),
+            Err(WebhookError::InvalidSignature)
+        ));
+    }
+
+    #[test]
+    fn refuses_webhooks_without_a_secret() {
+        let body = b"Hello, World!";
+        let disabled = WebhookConfig { secret: None };
+
+        assert!(matches!(
+            disabled.verify(Some(&sign(body)), body),
+            Err(WebhookError::Disabled)
+        ));
+    }
+
+    #[test]
+    fn reads_urls_of_pushes_to_the_default_branch() {
+        let urls = github_push_urls(Some("push"), &push("refs/heads/main", false))
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            urls,
+            [
+                "https://github.com/Octo/City.git",
+                "git@github.com:Octo/City.git",
+                "https://github.com/Octo/City",
+            ]
+        );
+    }
+
+    #[test]
+    fn ignores_other_branches_deletions_and_events() {
+        assert!(
+            github_push_urls(Some("push"), &push("refs/heads/feature", false))
+                .unwrap()
+                .is_none()
+        );
+        assert!(github_push_urls(Some("push"), &push("refs/tags/v1", false))
+            .unwrap()
+            .is_none());
+        assert!(
+            github_push_urls(Some("push"), &push("refs/heads/main", true))
+                .unwrap()
+                .is_none()
+        );
+        assert!(github_push_urls(Some("ping"), b"{}").unwrap().is_none());
+        assert!(github_push_urls(None, b"{}").unwrap().is_none());
+        assert!(matches!(
+            github_push_urls(Some("push"), b"not json"),
+            Err(WebhookError::Payload(_))
+        ));
+    }
+
+    #[test]
+    fn matches_urls_regardless_of_git_suffix_trailing_slash_and_case() {
+        let stored = analysis("https://github.com/octo/city.git", None);
+
+        for url in [
+            "https://github.com/octo/city.git",
+            "https://github.com/octo/city",
+            "https://github.com/octo/city/",
+            "https://github.com/Octo/City.git",
+            " HTTPS://GITHUB.COM/OCTO/CITY/ ",
+        ] {
+            assert!(matches_push(&stored, &[url.to_string()]), "{}", url);
+        }
+        assert!(!matches_push(
+            &stored,
+            &["https://github.com/octo/city-fork.git".to_string()]
+        ));
+        assert!(!matches_push(
+            &stored,
+            &["https://gitlab.com/octo/city.git".to_string()]
+        ));
+        assert!(!matches_push(&stored, &[]));
+    }
+
+    #[test]
+    fn matches_pushes_by_web_url() {
+        let stored = analysis("/var/cache/clone", Some("https://github.com/octo/city"));
+
+        assert!(matches_push(
+            &stored,
+            &["https://github.com/Octo/City/".to_string()]
+        ));
+        assert!(!matches_push(
+            &stored,
+            &["https://github.com/octo/other".to_string()]
+        ));
+    }
+}
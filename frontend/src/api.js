@@ -36,15 +36,21 @@ export async function analyzeGithubRepo(ownerRepo) {
 }
 
 export async function getRepos() {
+  // Summaries only; directory trees are loaded per repository with getRepoTree
   const response = await fetch(`${API_BASE}/repos`);
   if (!response.ok) {
     throw new Error('Failed to fetch repositories');
   }
-  return response.json();
+  const data = await response.json();
+  return data.repos;
 }
 
-export async function getRepoTree(id) {
-  const response = await fetch(`${API_BASE}/repo/${id}/tree`);
+export async function getRepoTree(id, { depth } = {}) {
+  const params = new URLSearchParams();
+  if (depth) {
+    params.set('depth', depth);
+  }
+  const response = await fetch(`${API_BASE}/repo/${id}/tree?${params}`);
   if (!response.ok) {
     throw new Error('Failed to fetch repository tree');
   }
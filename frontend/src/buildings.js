@@ -47,8 +47,9 @@ export function calculateBuildingDimensions(data, allData, compact = true) {
 }
 
 export function getPrimaryLanguageColor(data) {
-  if (data.languages && data.languages.length > 0) {
-    return data.languages[0].color || getLanguageColor(data.languages[0].language);
+  const languages = data.total_languages || data.languages;
+  if (languages && languages.length > 0) {
+    return languages[0].color || getLanguageColor(languages[0].language);
   }
   return '#8B8B8B';
 }
@@ -85,7 +86,7 @@ export function layoutBuildings(items) {
 
 export function layoutDirectories(directories, parentData) {
   // Flatten first level of directories for visualization
-  const items = directories.filter(d => d.lines > 0);
+  const items = directories.filter(d => (d.total_lines || d.lines) > 0);
   return layoutBuildings(items);
 }
 
@@ -102,8 +103,8 @@ export function layoutCityBlocks(repos) {
   const allDirs = [];
   repos.forEach(repo => {
     if (repo.directories) {
-      repo.directories.filter(d => d.lines > 0).forEach(dir => {
-        allDirs.push({ ...dir, repoId: repo.id, repoName: repo.name });
+      repo.directories.filter(d => (d.total_lines || d.lines) > 0).forEach(dir => {
+        allDirs.push({ ...dir, repoId: repo.id, repoName: repo.display_name || repo.name });
       });
     }
   });
@@ -113,7 +114,7 @@ export function layoutCityBlocks(repos) {
 
   // Calculate block sizes first
   const blockInfos = sortedRepos.map((repo) => {
-    const dirs = (repo.directories || []).filter(d => d.lines > 0);
+    const dirs = (repo.directories || []).filter(d => (d.total_lines || d.lines) > 0);
     const dirCount = Math.max(dirs.length, 1);
 
     // Limit directories shown per block for compactness
@@ -190,7 +191,7 @@ export function layoutCityBlocks(repos) {
       // Add block info for ground plane rendering
       result.blocks.push({
         repoId: repo.id,
-        repoName: repo.name,
+        repoName: repo.display_name || repo.name,
         position: { x: blockX, z: blockZ },
         width: blockWidth,
         depth: blockDepth,
@@ -206,7 +207,7 @@ export function layoutCityBlocks(repos) {
         const extendedDir = {
           ...dir,
           repoId: repo.id,
-          repoName: repo.name,
+          repoName: repo.display_name || repo.name,
         };
 
         const dimensions = calculateBuildingDimensions(extendedDir, allDirs, true);
@@ -1,7 +1,13 @@
 import { Scene } from './scene.js';
 import { UI } from './ui.js';
 import { layoutBuildings, layoutCityBlocks } from './buildings.js';
-import { analyzeLocalRepo, analyzeGithubRepo, getRepos, scanDirectory } from './api.js';
+import {
+  analyzeLocalRepo,
+  analyzeGithubRepo,
+  getRepos,
+  getRepoTree,
+  scanDirectory,
+} from './api.js';
 
 class CodeCity {
   constructor() {
@@ -161,7 +167,13 @@ class CodeCity {
         this.ui.updateRepoList(this.repos);
         this.renderRepoView();
         this.ui.clearScanPath();
-        alert(`Found and analyzed ${result.total_analyzed} repositories.`);
+        const failures = (result.failures || [])
+          .map((f) => `  ${f.path} (${f.error_kind}): ${f.message}`)
+          .join('\n');
+        alert(
+          `Found and analyzed ${result.total_analyzed} repositories.` +
+            (failures ? `\n\nFailed to analyze:\n${failures}` : '')
+        );
       }
     } catch (error) {
       alert(`Error: ${error.message}`);
@@ -207,11 +219,36 @@ class CodeCity {
     this.scene.focusOnBuildings();
   }
 
-  renderCityBlocksView() {
+  async loadDirectories() {
+    // Listed repositories come without directories; blocks only show the top level
+    const missing = this.repos.filter((r) => !r.directories);
+    if (missing.length === 0) return;
+
+    this.ui.showLoading(true);
+    try {
+      await Promise.all(
+        missing.map(async (repo) => {
+          try {
+            repo.directories = await getRepoTree(repo.id, { depth: 1 });
+          } catch (error) {
+            console.log(`No directory data for ${repo.name}`);
+          }
+        })
+      );
+    } finally {
+      this.ui.showLoading(false);
+    }
+  }
+
+  async renderCityBlocksView() {
     if (this.repos.length === 0) {
       return;
     }
 
+    await this.loadDirectories();
+    // The user may have switched back while trees were loading
+    if (this.currentView !== 'dirs') return;
+
     // Check if any repos have directories
     const reposWithDirs = this.repos.filter(
       (r) => r.directories && r.directories.length > 0
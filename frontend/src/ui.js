@@ -65,9 +65,10 @@ export class UI {
     this.elements.tooltipName.textContent = displayName;
     this.elements.tooltipLines.textContent = formatNumber(data.total_lines || data.lines || 0);
     this.elements.tooltipAge.textContent = formatAge(data.age_days || 0);
+    const languages = data.total_languages || data.languages;
     this.elements.tooltipLanguage.textContent =
-      data.languages && data.languages.length > 0
-        ? data.languages[0].language
+      languages && languages.length > 0
+        ? languages[0].language
         : 'Unknown';
 
     // Position tooltip
@@ -99,7 +100,7 @@ export class UI {
       const languageBar = this.createLanguageBar(repo.languages);
 
       item.innerHTML = `
-        <div class="repo-item-name">${repo.name}</div>
+        <div class="repo-item-name">${repo.display_name || repo.name}</div>
         <div class="repo-item-stats">
           ${formatNumber(repo.total_lines)} lines | ${formatAge(repo.age_days)} old
         </div>